@@ -19,6 +19,14 @@ impl<T: AsRef<[u8]> + MaybeSend + 'static> From<T> for BytesBox {
     }
 }
 
+impl BytesBox {
+    /// Returns the wrapped bytes.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        (*self.0).as_ref()
+    }
+}
+
 impl UserData for BytesBox {}
 
 /// A type that can represent either a Lua string or a `BytesBox` userdata.
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use mlua::{MetaMethod, UserData, UserDataFields, UserDataMethods, UserDataRegistry};
+
+/// The category of a [`DecodeError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    /// The input is not syntactically valid.
+    Syntax,
+    /// The input ended unexpectedly.
+    Eof,
+    /// The input is valid, but cannot be converted to the requested value.
+    Data,
+    /// Failure to read the input.
+    Io,
+}
+
+impl DecodeErrorKind {
+    /// Returns the name of this error kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecodeErrorKind::Syntax => "syntax",
+            DecodeErrorKind::Eof => "eof",
+            DecodeErrorKind::Data => "data",
+            DecodeErrorKind::Io => "io",
+        }
+    }
+}
+
+/// An error returned by the decode functions, with the location of the failure if known.
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    message: String,
+    kind: DecodeErrorKind,
+    line: Option<usize>,
+    column: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl DecodeError {
+    /// Creates a new `DecodeError` of the `data` kind without a location.
+    pub(crate) fn data(err: impl fmt::Display) -> Self {
+        DecodeError {
+            message: format!("{err:#}"),
+            kind: DecodeErrorKind::Data,
+            line: None,
+            column: None,
+            offset: None,
+        }
+    }
+
+    /// Creates a new `DecodeError` from a JSON error.
+    ///
+    /// The `input` is used to calculate the byte offset of the failure.
+    #[cfg(feature = "json")]
+    pub(crate) fn from_json(err: serde_json::Error, input: &[u8]) -> Self {
+        use serde_json::error::Category;
+
+        let kind = match err.classify() {
+            Category::Syntax => DecodeErrorKind::Syntax,
+            Category::Eof => DecodeErrorKind::Eof,
+            Category::Data => DecodeErrorKind::Data,
+            Category::Io => DecodeErrorKind::Io,
+        };
+        let (line, column) = (err.line(), err.column());
+        // Line 0 means that the location is not known
+        let location = (line > 0).then(|| {
+            let offset = line_offset(input, line) + column.saturating_sub(1);
+            (line, column, offset.min(input.len()))
+        });
+        DecodeError {
+            message: err.to_string(),
+            kind,
+            line: location.map(|(line, ..)| line),
+            column: location.map(|(_, column, _)| column),
+            offset: location.map(|(.., offset)| offset),
+        }
+    }
+
+    /// Creates a new `DecodeError` from a JSON5 error.
+    ///
+    /// The `input` is used to calculate the byte offset of the failure.
+    #[cfg(feature = "json")]
+    pub(crate) fn from_json5(err: json5::Error, input: &str) -> Self {
+        let json5::Error::Message { msg, location } = err;
+        let kind = match location {
+            Some(_) => DecodeErrorKind::Syntax,
+            None => DecodeErrorKind::Data,
+        };
+        // JSON5 columns are counted in characters
+        let offset = location.as_ref().map(|loc| {
+            let line_start = line_offset(input.as_bytes(), loc.line);
+            let column_len = (input[line_start..].chars())
+                .take(loc.column.saturating_sub(1))
+                .map(char::len_utf8)
+                .sum::<usize>();
+            line_start + column_len
+        });
+        DecodeError {
+            message: msg,
+            kind,
+            line: location.as_ref().map(|loc| loc.line),
+            column: location.as_ref().map(|loc| loc.column),
+            offset,
+        }
+    }
+
+    /// Creates a new `DecodeError` from a YAML error.
+    ///
+    /// The `input` is used to detect failures at the end of the input.
+    #[cfg(feature = "yaml")]
+    pub(crate) fn from_yaml(err: serde_yaml::Error, input: &[u8]) -> Self {
+        let location = err.location();
+        let kind = match &location {
+            // The parser fails at the end of the input only if it is incomplete
+            Some(loc) if loc.index() >= input.trim_ascii_end().len() => DecodeErrorKind::Eof,
+            Some(_) => DecodeErrorKind::Syntax,
+            None => DecodeErrorKind::Data,
+        };
+        DecodeError {
+            message: err.to_string(),
+            kind,
+            line: location.as_ref().map(|loc| loc.line()),
+            column: location.as_ref().map(|loc| loc.column()),
+            offset: location.as_ref().map(|loc| loc.index()),
+        }
+    }
+
+    /// Creates a new `DecodeError` from a YAML scanner error.
+    ///
+    /// The `input` is used to convert the character offset of the failure into a byte offset.
+    #[cfg(feature = "yaml")]
+    pub(crate) fn from_yaml_scan(err: saphyr_parser::ScanError, input: &str) -> Self {
+        let marker = *err.marker();
+        let offset = (input.char_indices().nth(marker.index())).map_or(input.len(), |(i, _)| i);
+        let kind = match offset == input.len() {
+            true => DecodeErrorKind::Eof,
+            false => DecodeErrorKind::Syntax,
+        };
+        // Scanner columns are 0-based
+        let (line, column) = (marker.line(), marker.col() + 1);
+        DecodeError {
+            message: format!("{} at line {line} column {column}", err.info()),
+            kind,
+            line: Some(line),
+            column: Some(column),
+            offset: Some(offset),
+        }
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.kind
+    }
+
+    /// Returns the 1-based line number where the error occurred.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the 1-based column number where the error occurred.
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+
+    /// Returns the 0-based byte offset from the start of the input where the error occurred.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+/// Returns the byte offset of the start of the given 1-based line.
+#[cfg(feature = "json")]
+fn line_offset(input: &[u8], line: usize) -> usize {
+    (input.split_inclusive(|&b| b == b'\n'))
+        .take(line.saturating_sub(1))
+        .map(|l| l.len())
+        .sum()
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl UserData for DecodeError {
+    fn register(registry: &mut UserDataRegistry<Self>) {
+        registry.add_field_method_get("message", |_, this| Ok(this.message.clone()));
+        registry.add_field_method_get("kind", |_, this| Ok(this.kind.as_str()));
+        registry.add_field_method_get("line", |_, this| Ok(this.line));
+        registry.add_field_method_get("column", |_, this| Ok(this.column));
+        registry.add_field_method_get("offset", |_, this| Ok(this.offset));
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.message.clone()));
+    }
+}
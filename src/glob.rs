@@ -0,0 +1,114 @@
+use std::result::Result as StdResult;
+
+use globset::Candidate;
+use mlua::{Lua, MetaMethod, Result, String as LuaString, Table, UserData, UserDataMethods};
+
+use crate::regex::Regex;
+
+/// A compiled glob pattern.
+#[derive(Clone, Debug)]
+pub struct Glob(globset::GlobMatcher);
+
+impl Glob {
+    /// Compiles a glob pattern using the given options table.
+    fn build(pattern: &str, opts: Option<&Table>) -> Result<StdResult<globset::Glob, globset::Error>> {
+        let mut builder = globset::GlobBuilder::new(pattern);
+        if let Some(yes) = opt_param!(opts, "case_insensitive")? {
+            builder.case_insensitive(yes);
+        }
+        if let Some(yes) = opt_param!(opts, "literal_separator")? {
+            builder.literal_separator(yes);
+        }
+        Ok(builder.build())
+    }
+}
+
+impl UserData for Glob {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("is_match", |_, this, path: LuaString| {
+            let path = path.as_bytes();
+            Ok(this.0.is_match_candidate(&Candidate::from_bytes(&*path)))
+        });
+
+        // Returns the original glob pattern.
+        registry.add_method("pattern", |_, this, ()| Ok(this.0.glob().glob().to_string()));
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(this.0.glob().glob().to_string())
+        });
+    }
+}
+
+/// A set of glob patterns matched in a single pass.
+#[derive(Clone, Debug)]
+pub struct GlobSet(globset::GlobSet);
+
+impl UserData for GlobSet {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("is_match", |_, this, path: LuaString| {
+            let path = path.as_bytes();
+            Ok(this.0.is_match_candidate(&Candidate::from_bytes(&*path)))
+        });
+
+        registry.add_method("len", |_, this, ()| Ok(this.0.len()));
+
+        registry.add_method("matches", |_, this, path: LuaString| {
+            let path = path.as_bytes();
+            Ok((this
+                .0
+                .matches_candidate(&Candidate::from_bytes(&*path))
+                .into_iter())
+            .map(|i| i + 1)
+            .collect::<Vec<_>>())
+        });
+    }
+}
+
+/// Compiles a glob pattern.
+///
+/// Supports `?`, `*`, `**`, character classes (`[ab]`, `[!ab]`) and alternates (`{a,b}`).
+///
+/// The optional `opts` table can contain:
+/// - `case_insensitive` (boolean): Match paths regardless of their case. Default is false.
+/// - `literal_separator` (boolean): If true, `*` and `?` do not match the path separator `/`.
+///   Default is false.
+pub fn compile(_: &Lua, (pattern, opts): (String, Option<Table>)) -> Result<StdResult<Glob, String>> {
+    let glob = lua_try!(Glob::build(&pattern, opts.as_ref())?);
+    Ok(Ok(Glob(glob.compile_matcher())))
+}
+
+/// Compiles a set of glob patterns.
+///
+/// Accepts the same options as `compile`, applied to every pattern.
+pub fn set(_: &Lua, (patterns, opts): (Vec<String>, Option<Table>)) -> Result<StdResult<GlobSet, String>> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(lua_try!(Glob::build(&pattern, opts.as_ref())?));
+    }
+    Ok(Ok(GlobSet(lua_try!(builder.build()))))
+}
+
+/// Converts a glob pattern to a compiled regular expression that matches the same paths.
+///
+/// Accepts the same options as `compile`.
+pub fn to_regex(lua: &Lua, (pattern, opts): (String, Option<Table>)) -> Result<StdResult<Regex, String>> {
+    let glob = lua_try!(Glob::build(&pattern, opts.as_ref())?);
+    Ok(Ok(lua_try!(Regex::new(lua, glob.regex()))))
+}
+
+/// A loader for the `glob` module.
+fn loader(lua: &Lua) -> Result<Table> {
+    let t = lua.create_table()?;
+    t.set("compile", lua.create_function(compile)?)?;
+    t.set("set", lua.create_function(set)?)?;
+    t.set("to_regex", lua.create_function(to_regex)?)?;
+    Ok(t)
+}
+
+/// Registers the `glob` module in the given Lua state.
+pub fn register(lua: &Lua, name: Option<&str>) -> Result<Table> {
+    let name = name.unwrap_or("@glob");
+    let value = loader(lua)?;
+    lua.register_module(name, &value)?;
+    Ok(value)
+}
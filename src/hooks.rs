@@ -0,0 +1,316 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::result::Result as StdResult;
+
+use mlua::{Error as LuaError, Function, Lua, LuaSerdeExt, Result, Table, Value};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+/// Encodes Lua values, applying custom serialization hooks.
+///
+/// Tables and userdata with one of the given `metamethods` are encoded as the result of calling it
+/// (only the first metamethod found is used). Then the optional `replacer(key, value)` function is
+/// called for every value (with an empty string as the key of the root value), and its result is
+/// encoded instead. Returning `nil` from the replacer removes the key, or stores `null` for array
+/// elements.
+///
+/// Hooks are called by the serializer as it goes, so values are never copied. Errors raised by the
+/// hooks are returned by [`Encoder::finish`].
+pub(crate) struct Encoder<'a> {
+    lua: &'a Lua,
+    metamethods: &'a [&'a str],
+    replacer: Option<&'a Function>,
+    relaxed: bool,
+    sort_keys: bool,
+    #[cfg(feature = "json")]
+    empty_tables_as_array: bool,
+    #[cfg(feature = "json")]
+    exact_numbers: bool,
+    #[cfg(feature = "yaml")]
+    yaml_tags: bool,
+    // Tables that are being encoded, to detect recursive tables
+    visited: RefCell<HashSet<*const c_void>>,
+    error: RefCell<Option<LuaError>>,
+}
+
+/// A Lua value (with hooks already applied) that can be serialized by an [`Encoder`].
+pub(crate) struct Serializable<'a> {
+    encoder: &'a Encoder<'a>,
+    value: Value,
+}
+
+impl<'a> Encoder<'a> {
+    pub(crate) fn new(lua: &'a Lua, metamethods: &'a [&'a str], replacer: Option<&'a Function>) -> Self {
+        Encoder {
+            lua,
+            metamethods,
+            replacer,
+            relaxed: false,
+            sort_keys: false,
+            #[cfg(feature = "json")]
+            empty_tables_as_array: false,
+            #[cfg(feature = "json")]
+            exact_numbers: false,
+            #[cfg(feature = "yaml")]
+            yaml_tags: false,
+            visited: RefCell::default(),
+            error: RefCell::default(),
+        }
+    }
+
+    /// Skips recursive tables and unsupported types instead of failing.
+    pub(crate) fn relaxed(mut self, enabled: bool) -> Self {
+        self.relaxed = enabled;
+        self
+    }
+
+    /// Encodes table keys in sorted order.
+    pub(crate) fn sort_keys(mut self, enabled: bool) -> Self {
+        self.sort_keys = enabled;
+        self
+    }
+
+    /// Encodes empty tables as arrays instead of maps.
+    #[cfg(feature = "json")]
+    pub(crate) fn empty_tables_as_array(mut self, enabled: bool) -> Self {
+        self.empty_tables_as_array = enabled;
+        self
+    }
+
+    /// Encodes json `Number`s and native objects with the exact text of big numbers.
+    ///
+    /// Only `serde_json` serializers support it.
+    #[cfg(feature = "json")]
+    pub(crate) fn exact_numbers(mut self, enabled: bool) -> Self {
+        self.exact_numbers = enabled;
+        self
+    }
+
+    /// Encodes yaml `Tagged` values and native objects with their tags.
+    ///
+    /// Only serializers into `serde_yaml::Value` (see `yaml::to_yaml_value`) support it.
+    #[cfg(feature = "yaml")]
+    pub(crate) fn yaml_tags(mut self, enabled: bool) -> Self {
+        self.yaml_tags = enabled;
+        self
+    }
+
+    /// Applies the hooks to the root value and returns it in a serializable form.
+    pub(crate) fn wrap(&'a self, value: Value) -> Result<Serializable<'a>> {
+        let key = match self.replacer {
+            Some(_) => Value::String(self.lua.create_string("")?),
+            None => Value::Nil,
+        };
+        let value = self.apply_hooks(key, value)?;
+        Ok(Serializable { encoder: self, value })
+    }
+
+    /// Returns a nested value in a serializable form, without applying the hooks.
+    #[cfg(feature = "yaml")]
+    pub(crate) fn serializable(&'a self, value: Value) -> Serializable<'a> {
+        Serializable { encoder: self, value }
+    }
+
+    /// Returns the error raised by a hook during serialization, or the serialization result.
+    pub(crate) fn finish<T, E>(&self, result: StdResult<T, E>) -> Result<StdResult<T, E>> {
+        match self.error.borrow_mut().take() {
+            Some(err) => Err(err),
+            None => Ok(result),
+        }
+    }
+
+    fn apply_hooks(&self, key: Value, mut value: Value) -> Result<Value> {
+        if let Some(hook) = self.find_metamethod(&value)? {
+            value = hook.call(value)?;
+        }
+        if let Some(replacer) = self.replacer {
+            value = replacer.call((key, value))?;
+        }
+        Ok(value)
+    }
+
+    /// Returns the first serialization metamethod defined for the value.
+    fn find_metamethod(&self, value: &Value) -> Result<Option<Function>> {
+        for name in self.metamethods {
+            let hook: Option<Function> = match value {
+                Value::Table(table) => match table.metatable() {
+                    Some(mt) => mt.raw_get(*name)?,
+                    None => return Ok(None),
+                },
+                Value::UserData(ud) => match ud.metatable() {
+                    Ok(mt) => mt.get(name)?,
+                    Err(_) => return Ok(None),
+                },
+                _ => return Ok(None),
+            };
+            if hook.is_some() {
+                return Ok(hook);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Keeps a Lua error to return it from [`Encoder::finish`].
+    fn fail<E: serde::ser::Error>(&self, err: LuaError) -> E {
+        let message = err.to_string();
+        self.error.borrow_mut().get_or_insert(err);
+        E::custom(message)
+    }
+
+    /// Checks whether a table element must be skipped (in relaxed mode) or is not allowed.
+    fn skip<E: serde::ser::Error>(&self, value: &Value) -> StdResult<bool, E> {
+        match value {
+            Value::Table(table) if self.visited.borrow().contains(&table.to_pointer()) => {
+                match self.relaxed {
+                    true => Ok(true),
+                    false => Err(E::custom("recursive table detected")),
+                }
+            }
+            Value::LightUserData(ud) if ud.0.is_null() => Ok(false),
+            Value::Function(_)
+            | Value::Thread(_)
+            | Value::LightUserData(_)
+            | Value::Error(_)
+            | Value::Other(_) => Ok(self.relaxed),
+            _ => Ok(false),
+        }
+    }
+
+    fn is_array(&self, table: &Table, len: usize) -> bool {
+        if len > 0 {
+            return true;
+        }
+        if let Some(mt) = table.metatable()
+            && mt.to_pointer() == self.lua.array_metatable().to_pointer()
+        {
+            return true;
+        }
+        #[cfg(feature = "json")]
+        if self.empty_tables_as_array && table.is_empty() {
+            return true;
+        }
+        false
+    }
+
+    fn serialize_table<S: Serializer>(&self, table: &Table, serializer: S) -> StdResult<S::Ok, S::Error> {
+        let ptr = table.to_pointer();
+        self.visited.borrow_mut().insert(ptr);
+        defer! {
+            self.visited.borrow_mut().remove(&ptr);
+        }
+
+        let len = table.raw_len();
+        if self.is_array(table, len) {
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            for i in 1..=len {
+                let value = table.raw_get(i).map_err(|err| self.fail(err))?;
+                let value = match self.apply_hooks(Value::Integer(i as _), value) {
+                    Ok(Value::Nil) => Value::NULL,
+                    Ok(value) => value,
+                    Err(err) => return Err(self.fail(err)),
+                };
+                if !self.skip(&value)? {
+                    seq.serialize_element(&Serializable { encoder: self, value })?;
+                }
+            }
+            return seq.end();
+        }
+
+        let mut pairs = table.pairs::<Value, Value>().collect::<Result<Vec<_>>>();
+        if self.sort_keys
+            && let Ok(pairs) = &mut pairs
+        {
+            pairs.sort_by(|(a, _), (b, _)| cmp_keys(a, b));
+        }
+        let mut map = serializer.serialize_map(None)?;
+        for (key, value) in pairs.map_err(|err| self.fail(err))? {
+            let value = self
+                .apply_hooks(key.clone(), value)
+                .map_err(|err| self.fail(err))?;
+            if value.is_nil() || self.skip(&key)? || self.skip(&value)? {
+                continue;
+            }
+            let key = key.to_serializable().deny_unsupported_types(!self.relaxed);
+            map.serialize_entry(&key, &Serializable { encoder: self, value })?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Serializable<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        #[cfg(feature = "json")]
+        let serializer = match &self.value {
+            Value::UserData(ud) if self.encoder.exact_numbers => {
+                match crate::json::serialize_exact(ud, serializer) {
+                    Ok(result) => return result,
+                    Err(serializer) => serializer,
+                }
+            }
+            _ => serializer,
+        };
+        #[cfg(feature = "yaml")]
+        let serializer = match &self.value {
+            Value::UserData(ud) if self.encoder.yaml_tags => {
+                match crate::yaml::serialize_tagged(ud, self.encoder, serializer) {
+                    Ok(result) => return result,
+                    Err(serializer) => serializer,
+                }
+            }
+            _ => serializer,
+        };
+
+        match &self.value {
+            Value::Table(table) => self.encoder.serialize_table(table, serializer),
+            value => (value.to_serializable())
+                .deny_unsupported_types(!self.encoder.relaxed)
+                .serialize(serializer),
+        }
+    }
+}
+
+/// Orders table keys: booleans first, then numbers, then strings (bytewise).
+fn cmp_keys(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Boolean(_) => 0,
+            Value::Integer(_) | Value::Number(_) => 1,
+            Value::String(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(_) | Value::Number(_), Value::Integer(_) | Value::Number(_)) => {
+            let (a, b) = (a.as_f64().unwrap_or_default(), b.as_f64().unwrap_or_default());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.as_bytes().cmp(&b.as_bytes()),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_pointer().cmp(&b.to_pointer())),
+    }
+}
+
+/// Applies the `reviver(key, value)` function to every value of a decoded Lua value.
+///
+/// Values are visited bottom-up, and the result of the reviver replaces the original value
+/// (returning `nil` removes the key). The root value is passed with an empty string as the key.
+pub(crate) fn revive(lua: &Lua, value: Value, reviver: &Function) -> Result<Value> {
+    revive_value(Value::String(lua.create_string("")?), value, reviver)
+}
+
+fn revive_value(key: Value, value: Value, reviver: &Function) -> Result<Value> {
+    if let Value::Table(table) = &value {
+        let pairs = table.pairs::<Value, Value>().collect::<Result<Vec<_>>>()?;
+        for (key, value) in pairs {
+            let new_value = revive_value(key.clone(), value, reviver)?;
+            table.raw_set(key, new_value)?;
+        }
+    }
+    reviver.call((key, value))
+}
@@ -5,7 +5,7 @@ use mlua::{
 };
 
 #[derive(Clone)]
-pub(crate) struct Headers(HeaderMap);
+pub(crate) struct Headers(pub(crate) HeaderMap);
 
 impl UserData for Headers {
     fn register(registry: &mut mlua::UserDataRegistry<Self>) {
@@ -1,9 +1,17 @@
-use mlua::{Lua, Result, Table};
+use http::Version;
+use mlua::{ExternalResult, Lua, Result, Table};
+
+use crate::bytes::{BytesBox, StringOrBytes};
+
+pub use request::Request;
+pub use response::Response;
 
 /// A loader for the `http` module.
 fn loader(lua: &Lua) -> Result<Table> {
     let t = lua.create_table()?;
     t.set("Headers", lua.create_proxy::<headers::Headers>()?)?;
+    t.set("Request", lua.create_proxy::<Request>()?)?;
+    t.set("Response", lua.create_proxy::<Response>()?)?;
     Ok(t)
 }
 
@@ -15,4 +23,23 @@ pub fn register(lua: &Lua, name: Option<&str>) -> Result<Table> {
     Ok(value)
 }
 
+/// Parses an HTTP version string (e.g. "HTTP/1.1").
+fn parse_version(version: &str) -> Result<Version> {
+    match version {
+        "HTTP/0.9" => Ok(Version::HTTP_09),
+        "HTTP/1.0" => Ok(Version::HTTP_10),
+        "HTTP/1.1" => Ok(Version::HTTP_11),
+        "HTTP/2.0" | "HTTP/2" => Ok(Version::HTTP_2),
+        "HTTP/3.0" | "HTTP/3" => Ok(Version::HTTP_3),
+        _ => Err(format!("invalid HTTP version '{version}'")).into_lua_err(),
+    }
+}
+
+/// Copies a Lua string or bytes into a message body.
+fn body_from_lua(body: StringOrBytes) -> BytesBox {
+    BytesBox::from(body.as_bytes_deref().to_vec())
+}
+
 mod headers;
+mod request;
+mod response;
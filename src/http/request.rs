@@ -0,0 +1,115 @@
+use http::{Method, Uri};
+use mlua::{
+    ErrorContext, ExternalResult, MetaMethod, Result, String as LuaString, Table, UserData, UserDataMethods,
+};
+
+use super::headers::Headers;
+use super::{body_from_lua, parse_version};
+use crate::bytes::{BytesBox, StringOrBytes};
+
+/// An HTTP request that can be passed to Lua as userdata.
+pub struct Request(http::Request<BytesBox>);
+
+impl Request {
+    /// Consumes the userdata, returning the wrapped `http::Request`.
+    pub fn into_inner(self) -> http::Request<BytesBox> {
+        self.0
+    }
+}
+
+impl From<http::Request<BytesBox>> for Request {
+    fn from(request: http::Request<BytesBox>) -> Self {
+        Request(request)
+    }
+}
+
+impl From<Request> for http::Request<BytesBox> {
+    fn from(request: Request) -> Self {
+        request.0
+    }
+}
+
+fn parse_method(method: LuaString) -> Result<Method> {
+    Method::from_bytes(&method.as_bytes()).into_lua_err()
+}
+
+fn parse_uri(uri: LuaString) -> Result<Uri> {
+    Uri::try_from(&*uri.as_bytes()).into_lua_err()
+}
+
+impl UserData for Request {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_function("new", |_, params: Option<Table>| {
+            let mut request = http::Request::new(BytesBox::from(Vec::new()));
+            if let Some(method) = opt_param!(params, "method")? {
+                *request.method_mut() = parse_method(method).context("invalid `method`")?;
+            }
+            if let Some(uri) = opt_param!(params, "uri")? {
+                *request.uri_mut() = parse_uri(uri).context("invalid `uri`")?;
+            }
+            if let Some(version) = opt_param!(params, "version")? {
+                let version: LuaString = version;
+                *request.version_mut() = parse_version(&version.to_str()?).context("invalid `version`")?;
+            }
+            if let Some(Headers(headers)) = opt_param!(params, "headers")? {
+                *request.headers_mut() = headers;
+            }
+            if let Some(body) = opt_param!(params, "body")? {
+                *request.body_mut() = body_from_lua(body);
+            }
+            Ok(Request(request))
+        });
+
+        registry.add_method("method", |_, this, ()| Ok(this.0.method().to_string()));
+
+        registry.add_method_mut("set_method", |_, this, method: LuaString| {
+            *this.0.method_mut() = parse_method(method)?;
+            Ok(())
+        });
+
+        registry.add_method("uri", |_, this, ()| Ok(this.0.uri().to_string()));
+
+        registry.add_method_mut("set_uri", |_, this, uri: LuaString| {
+            *this.0.uri_mut() = parse_uri(uri)?;
+            Ok(())
+        });
+
+        // Returns the path component of the URI.
+        registry.add_method("path", |_, this, ()| Ok(this.0.uri().path().to_string()));
+
+        // Returns the query string of the URI (without the leading `?`).
+        registry.add_method("query", |_, this, ()| {
+            Ok(this.0.uri().query().map(str::to_string))
+        });
+
+        registry.add_method("version", |_, this, ()| Ok(format!("{:?}", this.0.version())));
+
+        registry.add_method_mut("set_version", |_, this, version: LuaString| {
+            *this.0.version_mut() = parse_version(&version.to_str()?)?;
+            Ok(())
+        });
+
+        // Returns a copy of the request headers.
+        registry.add_method("headers", |_, this, ()| Ok(Headers(this.0.headers().clone())));
+
+        registry.add_method_mut("set_headers", |_, this, Headers(headers)| {
+            *this.0.headers_mut() = headers;
+            Ok(())
+        });
+
+        registry.add_method("body", |lua, this, ()| {
+            lua.create_string(this.0.body().as_bytes())
+        });
+
+        registry.add_method_mut("set_body", |_, this, body: StringOrBytes| {
+            *this.0.body_mut() = body_from_lua(body);
+            Ok(())
+        });
+
+        // Returns the request line, e.g. "GET /index.html HTTP/1.1"
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            let (method, uri, version) = (this.0.method(), this.0.uri(), this.0.version());
+            Ok(format!("{method} {uri} {version:?}"))
+        });
+    }
+}
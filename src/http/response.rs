@@ -0,0 +1,95 @@
+use http::StatusCode;
+use mlua::{
+    ErrorContext, ExternalResult, MetaMethod, Result, String as LuaString, Table, UserData, UserDataMethods,
+};
+
+use super::headers::Headers;
+use super::{body_from_lua, parse_version};
+use crate::bytes::{BytesBox, StringOrBytes};
+
+/// An HTTP response that can be passed to Lua as userdata.
+pub struct Response(http::Response<BytesBox>);
+
+impl Response {
+    /// Consumes the userdata, returning the wrapped `http::Response`.
+    pub fn into_inner(self) -> http::Response<BytesBox> {
+        self.0
+    }
+}
+
+impl From<http::Response<BytesBox>> for Response {
+    fn from(response: http::Response<BytesBox>) -> Self {
+        Response(response)
+    }
+}
+
+impl From<Response> for http::Response<BytesBox> {
+    fn from(response: Response) -> Self {
+        response.0
+    }
+}
+
+fn parse_status(status: u16) -> Result<StatusCode> {
+    StatusCode::from_u16(status).into_lua_err()
+}
+
+impl UserData for Response {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_function("new", |_, params: Option<Table>| {
+            let mut response = http::Response::new(BytesBox::from(Vec::new()));
+            if let Some(status) = opt_param!(params, "status")? {
+                *response.status_mut() = parse_status(status).context("invalid `status`")?;
+            }
+            if let Some(version) = opt_param!(params, "version")? {
+                let version: LuaString = version;
+                *response.version_mut() = parse_version(&version.to_str()?).context("invalid `version`")?;
+            }
+            if let Some(Headers(headers)) = opt_param!(params, "headers")? {
+                *response.headers_mut() = headers;
+            }
+            if let Some(body) = opt_param!(params, "body")? {
+                *response.body_mut() = body_from_lua(body);
+            }
+            Ok(Response(response))
+        });
+
+        registry.add_method("status", |_, this, ()| Ok(this.0.status().as_u16()));
+
+        registry.add_method_mut("set_status", |_, this, status: u16| {
+            *this.0.status_mut() = parse_status(status)?;
+            Ok(())
+        });
+
+        // Returns the canonical reason phrase of the status code, if known.
+        registry.add_method("reason", |_, this, ()| Ok(this.0.status().canonical_reason()));
+
+        registry.add_method("version", |_, this, ()| Ok(format!("{:?}", this.0.version())));
+
+        registry.add_method_mut("set_version", |_, this, version: LuaString| {
+            *this.0.version_mut() = parse_version(&version.to_str()?)?;
+            Ok(())
+        });
+
+        // Returns a copy of the response headers.
+        registry.add_method("headers", |_, this, ()| Ok(Headers(this.0.headers().clone())));
+
+        registry.add_method_mut("set_headers", |_, this, Headers(headers)| {
+            *this.0.headers_mut() = headers;
+            Ok(())
+        });
+
+        registry.add_method("body", |lua, this, ()| {
+            lua.create_string(this.0.body().as_bytes())
+        });
+
+        registry.add_method_mut("set_body", |_, this, body: StringOrBytes| {
+            *this.0.body_mut() = body_from_lua(body);
+            Ok(())
+        });
+
+        // Returns the status line, e.g. "HTTP/1.1 200 OK"
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(format!("{:?} {}", this.0.version(), this.0.status()))
+        });
+    }
+}
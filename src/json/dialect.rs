@@ -0,0 +1,119 @@
+use std::result::Result as StdResult;
+
+use mlua::{Error, FromLua, Lua, Result, Value};
+
+use super::value::JsonValue;
+use crate::error::DecodeError;
+
+/// The JSON dialect accepted by the decoder.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum Dialect {
+    /// Strict JSON (RFC 8259).
+    #[default]
+    Json,
+    /// JSON with comments and trailing commas.
+    Jsonc,
+    /// JSON5 (https://json5.org).
+    Json5,
+}
+
+impl FromLua for Dialect {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        match &*String::from_lua(value, lua)? {
+            "json" => Ok(Dialect::Json),
+            "jsonc" => Ok(Dialect::Jsonc),
+            "json5" => Ok(Dialect::Json5),
+            other => Err(Error::runtime(format!("unknown dialect '{other}'"))),
+        }
+    }
+}
+
+impl Dialect {
+    /// Checks whether the input has no values, only whitespace (and comments if allowed).
+    pub(crate) fn is_blank(self, data: &[u8]) -> bool {
+        match self {
+            Dialect::Json => data.iter().all(u8::is_ascii_whitespace),
+            Dialect::Jsonc | Dialect::Json5 => strip_jsonc(data).iter().all(u8::is_ascii_whitespace),
+        }
+    }
+
+    /// Parses the input according to this dialect.
+    ///
+    /// If `big_numbers` is set, numbers in JSON and JSONC input that Lua cannot represent without
+    /// loss keep their original text. JSON5 input does not, so it is rejected.
+    pub(crate) fn parse(self, data: &[u8], big_numbers: bool) -> StdResult<JsonValue, DecodeError> {
+        match self {
+            Dialect::Json => {
+                JsonValue::from_slice(data, big_numbers).map_err(|err| DecodeError::from_json(err, data))
+            }
+            Dialect::Jsonc => {
+                let data = strip_jsonc(data);
+                JsonValue::from_slice(&data, big_numbers).map_err(|err| DecodeError::from_json(err, &data))
+            }
+            Dialect::Json5 if big_numbers => Err(DecodeError::data("big numbers are not supported in JSON5")),
+            Dialect::Json5 => {
+                let text = str::from_utf8(data).map_err(DecodeError::data)?;
+                json5::from_str(text).map_err(|err| DecodeError::from_json5(err, text))
+            }
+        }
+    }
+}
+
+/// Replaces comments and trailing commas in JSONC input with spaces.
+///
+/// Newlines are kept, so error locations match the original input.
+fn strip_jsonc(data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    let mut i = 0;
+    // Last significant byte and the position of a comma that can be trailing
+    let mut last = 0u8;
+    let mut comma = None;
+    while i < out.len() {
+        match out[i] {
+            b'"' => {
+                i += 1;
+                while i < out.len() && out[i] != b'"' {
+                    if out[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                (last, comma) = (b'"', None);
+            }
+            b'/' if out.get(i + 1) == Some(&b'/') => {
+                while i < out.len() && out[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if out.get(i + 1) == Some(&b'*') => {
+                // Unterminated comments are left as is to be reported by the parser
+                let Some(len) = out[i + 2..].windows(2).position(|w| w == b"*/") else {
+                    break;
+                };
+                for b in &mut out[i..i + len + 4] {
+                    if *b != b'\n' {
+                        *b = b' ';
+                    }
+                }
+                i += len + 4;
+                continue;
+            }
+            b',' => {
+                comma = (!matches!(last, b'[' | b'{' | b',')).then_some(i);
+                last = b',';
+            }
+            b']' | b'}' => {
+                if let Some(pos) = comma.take() {
+                    out[pos] = b' ';
+                }
+                last = out[i];
+            }
+            b if b.is_ascii_whitespace() => {}
+            b => (last, comma) = (b, None),
+        }
+        i += 1;
+    }
+    out
+}
@@ -0,0 +1,133 @@
+use std::io::{self, Write};
+
+use serde_json::ser::{CompactFormatter, Formatter};
+
+/// A JSON formatter with configurable indentation, escaping and float formatting.
+#[derive(Default)]
+pub(crate) struct EncodeFormatter {
+    /// Indentation used for each nesting level. Output is compact if not set.
+    pub(crate) indent: Option<Vec<u8>>,
+    /// Escape non-ASCII characters as `\uXXXX` sequences.
+    pub(crate) ascii_only: bool,
+    /// Escape forward slashes as `\/`.
+    pub(crate) escape_slash: bool,
+    /// Number of digits after the decimal point for floats.
+    pub(crate) float_precision: Option<usize>,
+
+    current_indent: usize,
+    has_value: bool,
+}
+
+impl EncodeFormatter {
+    /// Returns true if the formatter produces the same output as the default compact one.
+    pub(crate) fn is_default(&self) -> bool {
+        self.indent.is_none() && !self.ascii_only && !self.escape_slash && self.float_precision.is_none()
+    }
+
+    fn write_newline<W: ?Sized + Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some(indent) = &self.indent {
+            writer.write_all(b"\n")?;
+            for _ in 0..self.current_indent {
+                writer.write_all(indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for EncodeFormatter {
+    fn write_f32<W: ?Sized + Write>(&mut self, writer: &mut W, value: f32) -> io::Result<()> {
+        self.write_f64(writer, value as f64)
+    }
+
+    fn write_f64<W: ?Sized + Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        match self.float_precision {
+            Some(precision) => write!(writer, "{value:.precision$}"),
+            None => CompactFormatter.write_f64(writer, value),
+        }
+    }
+
+    fn write_string_fragment<W: ?Sized + Write>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()> {
+        if !self.ascii_only && !self.escape_slash {
+            return writer.write_all(fragment.as_bytes());
+        }
+
+        let mut start = 0;
+        for (i, ch) in fragment.char_indices() {
+            let escape_slash = ch == '/' && self.escape_slash;
+            if ch.is_ascii() && !escape_slash {
+                continue;
+            }
+            writer.write_all(&fragment.as_bytes()[start..i])?;
+            start = i + ch.len_utf8();
+            if escape_slash {
+                writer.write_all(b"\\/")?;
+                continue;
+            }
+            // Non-ASCII characters are written as UTF-16 code units
+            for unit in ch.encode_utf16(&mut [0; 2]) {
+                write!(writer, "\\u{unit:04x}")?;
+            }
+        }
+        writer.write_all(&fragment.as_bytes()[start..])
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.current_indent += 1;
+        self.has_value = false;
+        writer.write_all(b"[")
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.current_indent -= 1;
+        if self.has_value {
+            self.write_newline(writer)?;
+        }
+        writer.write_all(b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        if !first {
+            writer.write_all(b",")?;
+        }
+        self.write_newline(writer)
+    }
+
+    fn end_array_value<W: ?Sized + Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.current_indent += 1;
+        self.has_value = false;
+        writer.write_all(b"{")
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.current_indent -= 1;
+        if self.has_value {
+            self.write_newline(writer)?;
+        }
+        writer.write_all(b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        if !first {
+            writer.write_all(b",")?;
+        }
+        self.write_newline(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        match self.indent {
+            Some(_) => writer.write_all(b": "),
+            None => writer.write_all(b":"),
+        }
+    }
+
+    fn end_object_value<W: ?Sized + Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,1174 @@
+use std::cell::RefCell;
+use std::collections::btree_map;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use mlua::{
+    AnyUserData, Either, Error as LuaError, Function, Integer as LuaInteger, IntoLuaMulti, Lua, LuaSerdeExt,
+    MetaMethod, MultiValue, Result, SerializeOptions, String as LuaString, Table, UserData, UserDataMethods,
+    UserDataRefMut, Value,
+};
+use ouroboros::self_referencing;
+use serde::{Serialize, Serializer, ser};
+use serde_json_path::{JsonPath, PathElement};
+
+use crate::bytes::StringOrBytes;
+use crate::error::DecodeError;
+use crate::hooks;
+use dialect::Dialect;
+use format::EncodeFormatter;
+use number::Number;
+use value::{BigNumbers, JsonValue};
+
+/// Metamethods that customize how tables and userdata are encoded.
+const ENCODE_METAMETHODS: &[&str] = &["__tojson", "__serialize"];
+
+/// Represents a native Json object in Lua.
+///
+/// All objects returned from the same document share it: changes made through any of them are
+/// visible to the others. Each object keeps track of its value when array elements are inserted
+/// or removed before it, and becomes invalid when the value (or one of its parents) is removed
+/// from the document or replaced.
+#[derive(Clone)]
+pub(crate) struct JsonObject {
+    doc: Rc<Document>,
+    location: Rc<Location>,
+    big_numbers: bool,
+}
+
+/// A document shared by native objects.
+struct Document {
+    root: RefCell<Arc<JsonValue>>,
+    // Locations of the objects that point into the document
+    locations: RefCell<Vec<Weak<Location>>>,
+}
+
+/// The path from the document root to the value of an object, or `None` if it was removed.
+type Location = RefCell<Option<Vec<PathSegment>>>;
+
+/// A step in the path from the document root to a value.
+#[derive(Clone, Debug, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A change of an array or object that moves or removes the values inside it.
+enum Change {
+    /// An element was inserted into the array at the given index.
+    Insert(usize),
+    /// The element at the given key (or index) was removed.
+    Remove(PathSegment),
+    /// The whole value was replaced.
+    Replace,
+}
+
+impl Document {
+    /// Returns a new location in the document, which is kept up to date with its changes.
+    fn locate(&self, path: Vec<PathSegment>) -> Rc<Location> {
+        let location = Rc::new(RefCell::new(Some(path)));
+        let mut locations = self.locations.borrow_mut();
+        if locations.len() == locations.capacity() {
+            locations.retain(|loc| loc.strong_count() > 0);
+        }
+        locations.push(Rc::downgrade(&location));
+        location
+    }
+
+    /// Updates the locations inside the value at the given path after it was changed.
+    fn update(&self, path: &[PathSegment], change: Change) {
+        for location in self.locations.borrow().iter().filter_map(Weak::upgrade) {
+            let mut location = location.borrow_mut();
+            let removed = match &mut *location {
+                Some(loc) if loc.len() > path.len() && loc.starts_with(path) => {
+                    match (&change, &mut loc[path.len()]) {
+                        (Change::Insert(i), PathSegment::Index(j)) if *j >= *i => {
+                            *j += 1;
+                            false
+                        }
+                        (Change::Remove(PathSegment::Index(i)), PathSegment::Index(j)) if *j > *i => {
+                            *j -= 1;
+                            false
+                        }
+                        (Change::Remove(removed), segment) => removed == segment,
+                        (Change::Replace, _) => true,
+                        _ => false,
+                    }
+                }
+                _ => false,
+            };
+            if removed {
+                *location = None;
+            }
+        }
+    }
+}
+
+/// A reference to a value in a version of the document.
+///
+/// The version is kept alive (and never modified) while the reference exists, so changes made to
+/// the document later are not visible through it.
+struct Snapshot {
+    _root: Arc<JsonValue>,
+    value: *const JsonValue,
+}
+
+impl Deref for Snapshot {
+    type Target = JsonValue;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.value }
+    }
+}
+
+impl Serialize for JsonObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        self.current().map_err(ser::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl JsonObject {
+    /// Creates a new `JsonObject` from the given JSON value.
+    ///
+    /// If `big_numbers` is set, numbers that cannot be represented by Lua numbers without loss are
+    /// returned as `Number` userdata.
+    fn new(value: JsonValue, big_numbers: bool) -> Self {
+        let doc = Rc::new(Document {
+            root: RefCell::new(Arc::new(value)),
+            locations: RefCell::default(),
+        });
+        let location = doc.locate(Vec::new());
+        JsonObject {
+            doc,
+            location,
+            big_numbers,
+        }
+    }
+
+    /// Returns a new `JsonObject` which points to the value at the given path in the same document.
+    fn with_path(&self, path: Vec<PathSegment>) -> Self {
+        JsonObject {
+            doc: self.doc.clone(),
+            location: self.doc.locate(path),
+            big_numbers: self.big_numbers,
+        }
+    }
+
+    /// Returns a new `JsonObject` which points to the child of the current value.
+    fn child(&self, segment: PathSegment) -> Result<Self> {
+        let mut path = self.path()?;
+        path.push(segment);
+        Ok(self.with_path(path))
+    }
+
+    /// Returns the path from the document root to the current value.
+    ///
+    /// Fails if the value was removed from the document.
+    fn path(&self) -> Result<Vec<PathSegment>> {
+        self.location.borrow().clone().ok_or_else(removed_error)
+    }
+
+    /// Updates the other objects of the document after the current value was changed.
+    fn changed(&self, change: Change) -> Result<()> {
+        self.doc.update(&self.path()?, change);
+        Ok(())
+    }
+
+    /// Returns the current value in the latest version of the document.
+    ///
+    /// Fails if the value was removed from the document.
+    fn current(&self) -> Result<Snapshot> {
+        let root = self.doc.root.borrow().clone();
+        let location = self.location.borrow();
+        let path = location.as_deref().ok_or_else(removed_error)?;
+        let value = resolve(&root, path).ok_or_else(removed_error)? as *const _;
+        Ok(Snapshot { _root: root, value })
+    }
+
+    /// Calls `f` with a mutable reference to the current value.
+    ///
+    /// The document is modified in place, so all objects that share it see the change. The data is
+    /// copied on write only while an older version is still in use (for example, by an iterator).
+    fn with_current_mut<R>(&self, f: impl FnOnce(&mut JsonValue) -> Result<R>) -> Result<R> {
+        let mut root = self.doc.root.borrow_mut();
+        let location = self.location.borrow();
+        let path = location.as_deref().ok_or_else(removed_error)?;
+        let value = resolve_mut(Arc::make_mut(&mut root), path).ok_or_else(removed_error)?;
+        f(value)
+    }
+
+    /// Returns a new `JsonObject` which points to the value at the given key.
+    ///
+    /// This operation is cheap and does not clone the underlying data.
+    fn get(&self, key: Value) -> Result<Option<Self>> {
+        let current = self.current()?;
+        let segment = match (&*current, key) {
+            (JsonValue::Array(vec), Value::Integer(index)) if index > 0 && index as usize <= vec.len() => {
+                PathSegment::Index(index as usize - 1)
+            }
+            (JsonValue::Object(map), Value::String(key)) => match key.to_str() {
+                Ok(key) if map.contains_key(&*key) => PathSegment::Key(key.to_owned()),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        self.child(segment).map(Some)
+    }
+
+    /// Returns a new `JsonObject` by following the given JSON Pointer path from the current value.
+    fn pointer(&self, pointer: &str) -> Result<Option<Self>> {
+        let Some(relative) = pointer_path(&*self.current()?, pointer) else {
+            return Ok(None);
+        };
+        let mut path = self.path()?;
+        path.extend(relative);
+        Ok(Some(self.with_path(path)))
+    }
+
+    /// Returns the type name of the current value.
+    fn type_name(&self) -> Result<&'static str> {
+        Ok(match &*self.current()? {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "boolean",
+            JsonValue::Number(_) | JsonValue::BigNumber(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        })
+    }
+
+    /// Returns the number of elements in an array or the number of keys in an object.
+    fn len(&self) -> Result<usize> {
+        Ok(match &*self.current()? {
+            JsonValue::Array(vec) => vec.len(),
+            JsonValue::Object(map) => map.len(),
+            _ => 0,
+        })
+    }
+
+    /// Returns the keys of an object, or the (1-based) indices of an array.
+    fn keys(&self, lua: &Lua) -> Result<Table> {
+        match &*self.current()? {
+            JsonValue::Array(vec) => lua.create_sequence_from(1..=vec.len()),
+            JsonValue::Object(map) => lua.create_sequence_from(map.keys().map(|k| k.as_str())),
+            _ => lua.create_table(),
+        }
+    }
+
+    /// Returns all values matching the given JSONPath query, relative to the current value.
+    ///
+    /// The matches point into the same document and do not clone the underlying data.
+    fn query(&self, path: &JsonPath) -> Result<Vec<Self>> {
+        let current = self.current()?;
+        let base = self.path()?;
+        let matches = (path.query_located(&current.to_serde()).iter())
+            .map(|node| {
+                let mut path = base.clone();
+                path.extend(node.location().iter().map(|element| match element {
+                    PathElement::Name(name) => PathSegment::Key(name.to_string()),
+                    PathElement::Index(index) => PathSegment::Index(*index),
+                }));
+                self.with_path(path)
+            })
+            .collect();
+        Ok(matches)
+    }
+
+    /// Sets the value at the given JSON Pointer path, relative to the current value.
+    ///
+    /// The parent of the target location must exist. For arrays, the last path segment can be an
+    /// index within the array bounds, the array length or `-` to append a new element.
+    fn set(&self, path: &str, value: JsonValue) -> Result<()> {
+        let Some((parent_path, token)) = path.rsplit_once('/') else {
+            if !path.is_empty() {
+                return Err(LuaError::runtime(format!("invalid JSON pointer '{path}'")));
+            }
+            self.with_current_mut(|current| {
+                *current = value;
+                Ok(())
+            })?;
+            return self.changed(Change::Replace);
+        };
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let parent = match self.pointer(parent_path)? {
+            Some(parent) => parent,
+            None => return Err(LuaError::runtime(format!("path '{parent_path}' does not exist"))),
+        };
+
+        let replaced = parent.with_current_mut(|current| match current {
+            JsonValue::Object(map) => {
+                map.insert(token.clone(), value);
+                Ok(Some(PathSegment::Key(token)))
+            }
+            JsonValue::Array(vec) => match token.parse::<usize>() {
+                Ok(i) if i < vec.len() => {
+                    vec[i] = value;
+                    Ok(Some(PathSegment::Index(i)))
+                }
+                Ok(i) if i == vec.len() => {
+                    vec.push(value);
+                    Ok(None)
+                }
+                _ if token == "-" => {
+                    vec.push(value);
+                    Ok(None)
+                }
+                _ => Err(LuaError::runtime(format!(
+                    "array index '{token}' is out of bounds"
+                ))),
+            },
+            _ => {
+                let err = format!("cannot set '{token}' on a primitive value at '{parent_path}'");
+                Err(LuaError::runtime(err))
+            }
+        })?;
+        match replaced {
+            Some(segment) => parent.child(segment)?.changed(Change::Replace),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the value at the given key, returning it.
+    ///
+    /// Array elements after the removed one are shifted down.
+    fn remove(&self, key: Value) -> Result<Option<JsonValue>> {
+        let removed = self.with_current_mut(|current| match (current, key) {
+            (JsonValue::Object(map), Value::String(key)) => {
+                let key = key.to_str()?.to_owned();
+                Ok(map.remove(&key).map(|value| (PathSegment::Key(key), value)))
+            }
+            (JsonValue::Array(vec), Value::Integer(i)) => {
+                if i < 1 || i as usize > vec.len() {
+                    return Ok(None);
+                }
+                Ok(Some((
+                    PathSegment::Index(i as usize - 1),
+                    vec.remove(i as usize - 1),
+                )))
+            }
+            (JsonValue::Object(_) | JsonValue::Array(_), key) => Err(LuaError::runtime(format!(
+                "invalid key type '{}'",
+                key.type_name()
+            ))),
+            _ => Err(LuaError::runtime("cannot remove from a primitive value")),
+        })?;
+        let Some((segment, value)) = removed else {
+            return Ok(None);
+        };
+        self.changed(Change::Remove(segment))?;
+        Ok(Some(value))
+    }
+
+    /// Appends a value to the end of the array.
+    fn push(&self, value: JsonValue) -> Result<()> {
+        self.with_current_mut(|current| match current {
+            JsonValue::Array(vec) => {
+                vec.push(value);
+                Ok(())
+            }
+            _ => Err(LuaError::runtime("cannot push into a non-array value")),
+        })
+    }
+
+    /// Inserts a value into the array at the given (1-based) position.
+    ///
+    /// Array elements from this position are shifted up.
+    fn insert(&self, index: LuaInteger, value: JsonValue) -> Result<()> {
+        self.with_current_mut(|current| match current {
+            JsonValue::Array(vec) if index >= 1 && index as usize <= vec.len() + 1 => {
+                vec.insert(index as usize - 1, value);
+                Ok(())
+            }
+            JsonValue::Array(_) => Err(LuaError::runtime(format!("index {index} is out of bounds"))),
+            _ => Err(LuaError::runtime("cannot insert into a non-array value")),
+        })?;
+        self.changed(Change::Insert(index as usize - 1))
+    }
+
+    /// Assigns a value to the given key, appending it if the key is the next array index.
+    fn assign(&self, key: Value, value: JsonValue) -> Result<()> {
+        let replaced = self.with_current_mut(|current| match (current, key) {
+            (JsonValue::Object(map), Value::String(key)) => {
+                let key = key.to_str()?.to_owned();
+                map.insert(key.clone(), value);
+                Ok(Some(PathSegment::Key(key)))
+            }
+            (JsonValue::Array(vec), Value::Integer(i)) if i >= 1 && i as usize <= vec.len() => {
+                vec[i as usize - 1] = value;
+                Ok(Some(PathSegment::Index(i as usize - 1)))
+            }
+            (JsonValue::Array(vec), Value::Integer(i)) if i as usize == vec.len() + 1 => {
+                vec.push(value);
+                Ok(None)
+            }
+            (JsonValue::Array(_), Value::Integer(i)) => {
+                Err(LuaError::runtime(format!("index {i} is out of bounds")))
+            }
+            (JsonValue::Object(_) | JsonValue::Array(_), key) => Err(LuaError::runtime(format!(
+                "invalid key type '{}'",
+                key.type_name()
+            ))),
+            _ => Err(LuaError::runtime("cannot assign to a primitive value")),
+        })?;
+        match replaced {
+            Some(segment) => self.child(segment)?.changed(Change::Replace),
+            None => Ok(()),
+        }
+    }
+
+    /// Converts this `JsonObject` into a Lua `Value`.
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        let current = self.current()?;
+        self.value_into_lua(lua, &current, || Ok(self.clone()))
+    }
+
+    /// Converts a value of this document into a Lua `Value`.
+    ///
+    /// Arrays and objects are returned as the `JsonObject` created by `obj`.
+    fn value_into_lua(
+        &self,
+        lua: &Lua,
+        value: &JsonValue,
+        obj: impl FnOnce() -> Result<Self>,
+    ) -> Result<Value> {
+        match value {
+            JsonValue::Null => Ok(Value::NULL),
+            JsonValue::Bool(b) => Ok(Value::Boolean(*b)),
+            JsonValue::Number(n) => Ok(number_to_lua(n)),
+            JsonValue::BigNumber(n) => n.to_lua(lua, self.big_numbers),
+            JsonValue::String(s) => Ok(Value::String(lua.create_string(s)?)),
+            JsonValue::Array(_) | JsonValue::Object(_) => {
+                Ok(Value::UserData(lua.create_ser_userdata(obj()?)?))
+            }
+        }
+    }
+
+    fn lua_iterator(&self, lua: &Lua) -> Result<MultiValue> {
+        let current = self.current()?;
+        if current.is_array() {
+            let next = Self::lua_array_iterator(lua)?;
+            let iter_ud = AnyUserData::wrap(LuaJsonArrayIter {
+                value: self.clone(),
+                next: 1, // index starts at 1
+            });
+            (next, iter_ud).into_lua_multi(lua)
+        } else if current.is_object() {
+            let next = Self::lua_map_iterator(lua)?;
+            // Iterate over the current version of the document, even if it is modified later
+            let iter_builder = LuaJsonMapIterBuilder {
+                value: self.clone(),
+                current,
+                iter_builder: |current| current.as_object().unwrap().iter(),
+            };
+            let iter_ud = AnyUserData::wrap(iter_builder.build());
+            (next, iter_ud).into_lua_multi(lua)
+        } else {
+            ().into_lua_multi(lua)
+        }
+    }
+
+    /// Returns an iterator function for arrays.
+    fn lua_array_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__json_array_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<LuaJsonArrayIter>| {
+            it.next += 1;
+            match it.value.get(Value::Integer(it.next - 1))? {
+                Some(next_value) => (it.next - 1, next_value.into_lua(lua)?).into_lua_multi(lua),
+                None => ().into_lua_multi(lua),
+            }
+        })?;
+        lua.set_named_registry_value("__json_array_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Returns an iterator function for objects.
+    fn lua_map_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__json_map_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<LuaJsonMapIter>| {
+            let obj = it.borrow_value().clone();
+            it.with_iter_mut(move |iter| match iter.next() {
+                Some((key, value)) => {
+                    let value =
+                        obj.value_into_lua(lua, value, || obj.child(PathSegment::Key(key.clone())))?;
+                    (lua.create_string(key)?, value).into_lua_multi(lua)
+                }
+                None => ().into_lua_multi(lua),
+            })
+        })?;
+        lua.set_named_registry_value("__json_map_iterator", &f)?;
+        Ok(f)
+    }
+}
+
+/// Returns the error for objects whose value was removed from the document.
+fn removed_error() -> LuaError {
+    LuaError::runtime("value was removed from the document")
+}
+
+/// Parses an array index in a JSON Pointer.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') || token.starts_with('+') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Follows the path from the given value.
+fn resolve<'a>(mut value: &'a JsonValue, path: &[PathSegment]) -> Option<&'a JsonValue> {
+    for segment in path {
+        value = match segment {
+            PathSegment::Key(key) => value.as_object()?.get(key)?,
+            PathSegment::Index(index) => value.as_array()?.get(*index)?,
+        };
+    }
+    Some(value)
+}
+
+/// Follows a JSON Pointer from the given value, returning the path to the target value.
+fn pointer_path(mut value: &JsonValue, pointer: &str) -> Option<Vec<PathSegment>> {
+    let mut path = Vec::new();
+    if pointer.is_empty() {
+        return Some(path);
+    }
+    for token in pointer.strip_prefix('/')?.split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let segment = match value {
+            JsonValue::Object(map) => {
+                value = map.get(&token)?;
+                PathSegment::Key(token)
+            }
+            JsonValue::Array(vec) => {
+                let index = parse_index(&token)?;
+                value = vec.get(index)?;
+                PathSegment::Index(index)
+            }
+            _ => return None,
+        };
+        path.push(segment);
+    }
+    Some(path)
+}
+
+/// Follows the path from the given value, returning a mutable reference.
+fn resolve_mut<'a>(mut value: &'a mut JsonValue, path: &[PathSegment]) -> Option<&'a mut JsonValue> {
+    for segment in path {
+        value = match segment {
+            PathSegment::Key(key) => value.as_object_mut()?.get_mut(key)?,
+            PathSegment::Index(index) => value.as_array_mut()?.get_mut(*index)?,
+        };
+    }
+    Some(value)
+}
+
+impl UserData for JsonObject {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("pointer", |lua, this, path: LuaString| {
+            this.pointer(&path.to_str()?)?
+                .map(|obj| obj.into_lua(lua))
+                .unwrap_or(Ok(Value::Nil))
+        });
+
+        registry.add_method("keys", |lua, this, ()| this.keys(lua));
+
+        // Returns the type of the current value or the value at the given key
+        registry.add_method("type", |_, this, key: Option<Value>| match key {
+            Some(key) => this.get(key)?.map(|obj| obj.type_name()).transpose(),
+            None => Ok(Some(this.type_name()?)),
+        });
+
+        registry.add_method("is_null", |_, this, key: Option<Value>| match key {
+            Some(key) => match this.get(key)? {
+                Some(obj) => Ok(obj.current()?.is_null()),
+                None => Ok(false),
+            },
+            None => Ok(this.current()?.is_null()),
+        });
+
+        registry.add_method("query", |lua, this, path: LuaString| {
+            let path = lua_try!(JsonPath::parse(&path.to_str()?));
+            let matches = (this.query(&path)?.into_iter())
+                .map(|obj| obj.into_lua(lua))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Ok(lua.create_sequence_from(matches)?))
+        });
+
+        registry.add_method("query_one", |lua, this, path: LuaString| {
+            let path = lua_try!(JsonPath::parse(&path.to_str()?));
+            match this.query(&path)?.into_iter().next() {
+                Some(obj) => Ok(Ok(obj.into_lua(lua)?)),
+                None => Ok(Ok(Value::Nil)),
+            }
+        });
+
+        registry.add_method("set", |lua, this, (path, value): (LuaString, Value)| {
+            this.set(&path.to_str()?, lua_to_json(lua, value)?)
+        });
+
+        registry.add_method("remove", |lua, this, key: Value| match this.remove(key)? {
+            Some(value) => JsonObject::new(value, this.big_numbers).into_lua(lua),
+            None => Ok(Value::Nil),
+        });
+
+        registry.add_method("push", |lua, this, value: Value| {
+            this.push(lua_to_json(lua, value)?)
+        });
+
+        registry.add_method("insert", |lua, this, (index, value): (LuaInteger, Value)| {
+            this.insert(index, lua_to_json(lua, value)?)
+        });
+
+        registry.add_method("dump", |lua, this, ()| {
+            json_value_to_lua(lua, &*this.current()?, SerializeOptions::new(), this.big_numbers)
+        });
+
+        registry.add_method("iter", |lua, this, ()| this.lua_iterator(lua));
+
+        registry.add_meta_method(MetaMethod::Index, |lua, this, key: Value| {
+            this.get(key)?
+                .map(|obj| obj.into_lua(lua))
+                .unwrap_or(Ok(Value::Nil))
+        });
+
+        // Assigning `nil` removes the key (or the array element)
+        registry.add_meta_method(MetaMethod::NewIndex, |lua, this, (key, value): (Value, Value)| {
+            if value.is_nil() {
+                return this.remove(key).map(|_| ());
+            }
+            this.assign(key, lua_to_json(lua, value)?)
+        });
+
+        registry.add_meta_method(MetaMethod::Len, |_, this, ()| this.len());
+
+        registry.add_meta_function(MetaMethod::Eq, |_, (a, b): (AnyUserData, AnyUserData)| {
+            match (a.borrow::<Self>(), b.borrow::<Self>()) {
+                (Ok(a), Ok(b)) => Ok(*a.current()? == *b.current()?),
+                _ => Ok(false),
+            }
+        });
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            serde_json::to_string(&this.current()?.exact()).map_err(LuaError::external)
+        });
+
+        registry.add_meta_method(crate::METAMETHOD_ITER, |lua, this, ()| this.lua_iterator(lua));
+    }
+}
+
+struct LuaJsonArrayIter {
+    value: JsonObject,
+    next: LuaInteger,
+}
+
+#[self_referencing]
+struct LuaJsonMapIter {
+    value: JsonObject,
+    current: Snapshot,
+
+    #[borrows(current)]
+    #[covariant]
+    iter: btree_map::Iter<'this, String, JsonValue>,
+}
+
+/// Source of records for the JSON Lines iterator.
+enum LinesSource {
+    /// A complete buffer and the offset of the next record.
+    Buffer(StringOrBytes, usize),
+    /// A reader function that returns chunks of data (or `nil` when exhausted).
+    Reader {
+        read: Function,
+        buf: Vec<u8>,
+        pos: usize,
+        eof: bool,
+    },
+}
+
+impl LinesSource {
+    /// Passes the next line (without the trailing newline) to `f`.
+    ///
+    /// Returns `None` when the source is exhausted.
+    fn next_line<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> Result<Option<R>> {
+        match self {
+            LinesSource::Buffer(data, pos) => {
+                let data = data.as_bytes_deref();
+                if *pos >= data.len() {
+                    return Ok(None);
+                }
+                let rest = &data[*pos..];
+                let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+                *pos += len + 1;
+                Ok(Some(f(&rest[..len])))
+            }
+            LinesSource::Reader { read, buf, pos, eof } => loop {
+                if let Some(len) = buf[*pos..].iter().position(|&b| b == b'\n') {
+                    let res = f(&buf[*pos..*pos + len]);
+                    *pos += len + 1;
+                    return Ok(Some(res));
+                }
+                if *eof {
+                    if *pos >= buf.len() {
+                        return Ok(None);
+                    }
+                    let res = f(&buf[*pos..]);
+                    *pos = buf.len();
+                    return Ok(Some(res));
+                }
+                // Discard consumed data and ask the reader for more
+                buf.drain(..*pos);
+                *pos = 0;
+                match read.call::<Option<StringOrBytes>>(())? {
+                    Some(chunk) => buf.extend_from_slice(&chunk.as_bytes_deref()),
+                    None => *eof = true,
+                }
+            },
+        }
+    }
+}
+
+struct LuaJsonLinesIter {
+    source: LinesSource,
+    line: usize,
+    native: bool,
+    options: DecodeOptions,
+}
+
+impl LuaJsonLinesIter {
+    /// Returns an iterator function for JSON Lines records.
+    fn lua_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__json_lines_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<LuaJsonLinesIter>| {
+            let it = &mut *it;
+            loop {
+                it.line += 1;
+                let line = it.line;
+                let options = &it.options;
+                let record = it.source.next_line(|bytes| {
+                    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+                    // Blank lines (and lines with only comments) are skipped
+                    if options.dialect.is_blank(bytes) {
+                        return None;
+                    }
+                    Some(options.dialect.parse(bytes, options.big_numbers))
+                })?;
+                let json = match record {
+                    None => return ().into_lua_multi(lua),
+                    Some(None) => continue,
+                    Some(Some(Ok(json))) => json,
+                    Some(Some(Err(err))) => {
+                        return (line, Value::Nil, format!("line {line}: {err}")).into_lua_multi(lua);
+                    }
+                };
+                let value = match it.native {
+                    true => JsonObject::new(json, it.options.big_numbers).into_lua(lua),
+                    false => it.options.to_lua(lua, &json),
+                };
+                return match value {
+                    Ok(value) => (line, value).into_lua_multi(lua),
+                    Err(err) => (line, Value::Nil, format!("line {line}: {err:#}")).into_lua_multi(lua),
+                };
+            }
+        })?;
+        lua.set_named_registry_value("__json_lines_iterator", &f)?;
+        Ok(f)
+    }
+}
+
+/// Options for decoding JSON into Lua values.
+struct DecodeOptions {
+    options: SerializeOptions,
+    dialect: Dialect,
+    big_numbers: bool,
+    reviver: Option<Function>,
+}
+
+impl DecodeOptions {
+    /// Builds the options from the `decode` options table.
+    fn new(opts: Option<&Table>) -> Result<Self> {
+        let mut options = SerializeOptions::new();
+        if let Some(enabled) = opts.and_then(|t| t.raw_get::<bool>("set_array_metatable").ok()) {
+            options = options.set_array_metatable(enabled);
+        }
+        if let Some(enabled) = opts.and_then(|t| t.raw_get::<bool>("null_as_nil").ok()) {
+            options = options
+                .serialize_unit_to_null(!enabled)
+                .serialize_none_to_null(!enabled);
+        }
+        let dialect: Option<Dialect> = opt_param!(opts, "dialect")?;
+        let big_numbers: Option<bool> = opt_param!(opts, "big_numbers")?;
+        let reviver: Option<Function> = opt_param!(opts, "reviver")?;
+        Ok(DecodeOptions {
+            options,
+            dialect: dialect.unwrap_or_default(),
+            big_numbers: big_numbers.unwrap_or(false),
+            reviver,
+        })
+    }
+
+    /// Decodes the input into a Lua value.
+    fn decode(&self, lua: &Lua, data: &[u8]) -> Result<StdResult<Value, DecodeError>> {
+        match self.dialect.parse(data, self.big_numbers) {
+            Ok(json) => Ok(Ok(self.to_lua(lua, &json)?)),
+            Err(err) => Ok(Err(err)),
+        }
+    }
+
+    /// Converts a decoded JSON value into a Lua value, applying the `reviver` function.
+    fn to_lua(&self, lua: &Lua, json: &JsonValue) -> Result<Value> {
+        let value = json_value_to_lua(lua, json, self.options, self.big_numbers)?;
+        match &self.reviver {
+            Some(reviver) => hooks::revive(lua, value, reviver),
+            None => Ok(value),
+        }
+    }
+}
+
+/// Converts a JSON value into a Lua value.
+///
+/// If `big_numbers` is set, numbers that cannot be represented by Lua numbers without loss are
+/// converted into `Number` userdata.
+fn json_value_to_lua(
+    lua: &Lua,
+    value: &JsonValue,
+    options: SerializeOptions,
+    big_numbers: bool,
+) -> Result<Value> {
+    match value {
+        JsonValue::Null if options.serialize_unit_to_null => Ok(Value::NULL),
+        JsonValue::Null => Ok(Value::Nil),
+        JsonValue::Bool(b) => Ok(Value::Boolean(*b)),
+        JsonValue::Number(n) => Ok(number_to_lua(n)),
+        JsonValue::BigNumber(n) => n.to_lua(lua, big_numbers),
+        JsonValue::String(s) => Ok(Value::String(lua.create_string(s)?)),
+        JsonValue::Array(vec) => {
+            let table = lua.create_table_with_capacity(vec.len(), 0)?;
+            for (i, value) in vec.iter().enumerate() {
+                table.raw_set(i + 1, json_value_to_lua(lua, value, options, big_numbers)?)?;
+            }
+            if options.set_array_metatable {
+                table.set_metatable(Some(lua.array_metatable()))?;
+            }
+            Ok(Value::Table(table))
+        }
+        JsonValue::Object(map) => {
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            for (key, value) in map {
+                table.raw_set(key.as_str(), json_value_to_lua(lua, value, options, big_numbers)?)?;
+            }
+            Ok(Value::Table(table))
+        }
+    }
+}
+
+/// Decodes a JSON string or bytes into a Lua value.
+///
+/// The optional `opts` table can contain:
+/// - `set_array_metatable` (boolean): If true, sets a metatable for arrays. Default is false.
+/// - `null_as_nil` (boolean): If true, `null`s will be represented as Lua `nil`. Default is false.
+/// - `dialect` (string): The accepted JSON dialect: "json", "jsonc" (comments and trailing commas)
+///   or "json5". Default is "json".
+/// - `big_numbers` (boolean): If true, integers outside of the 64-bit range and floats that cannot
+///   be represented exactly are decoded as `Number` userdata that keep the original text.
+///   Not supported for JSON5. Default is false.
+/// - `reviver` (function): A `reviver(key, value)` function that is called for every decoded value
+///   (from the innermost to the root) and returns the value to use instead.
+///
+/// On failure, returns `nil` and a [`DecodeError`] with the location of the error.
+pub fn decode(
+    lua: &Lua,
+    (data, opts): (StringOrBytes, Option<Table>),
+) -> Result<StdResult<Value, DecodeError>> {
+    DecodeOptions::new(opts.as_ref())?.decode(lua, &data.as_bytes_deref())
+}
+
+/// Decodes a JSON string or bytes as a native Rust object.
+///
+/// The returned value can be a primitive type or userdata.
+///
+/// The optional `opts` table can contain:
+/// - `dialect` (string): The accepted JSON dialect, see [`decode`].
+/// - `big_numbers` (boolean): If true, decode big numbers as `Number` userdata, see [`decode`].
+pub fn decode_native(
+    lua: &Lua,
+    (data, opts): (StringOrBytes, Option<Table>),
+) -> Result<StdResult<Value, DecodeError>> {
+    let dialect: Option<Dialect> = opt_param!(opts, "dialect")?;
+    let big_numbers: Option<bool> = opt_param!(opts, "big_numbers")?;
+    let big_numbers = big_numbers.unwrap_or(false);
+    let json = match dialect
+        .unwrap_or_default()
+        .parse(&data.as_bytes_deref(), big_numbers)
+    {
+        Ok(json) => json,
+        Err(err) => return Ok(Err(err)),
+    };
+    Ok(Ok(lua_try!(
+        JsonObject::new(json, big_numbers).into_lua(lua),
+        DecodeError::data
+    )))
+}
+
+/// Returns an iterator over newline-delimited JSON (JSON Lines) records.
+///
+/// The `source` can be a string, bytes or a reader function that returns the next chunk of data
+/// (or `nil` when there is no more data). Each iteration yields the line number and decoded value,
+/// or the line number, `nil` and an error message if the record is invalid. Blank lines are skipped.
+///
+/// The optional `opts` table accepts the same options as [`decode`], plus:
+/// - `native` (boolean): If true, records are decoded as native objects (see [`decode_native`]),
+///   which do not support `reviver`. Default is false.
+pub fn lines(
+    lua: &Lua,
+    (source, opts): (Either<StringOrBytes, Function>, Option<Table>),
+) -> Result<MultiValue> {
+    let source = match source {
+        Either::Left(data) => LinesSource::Buffer(data, 0),
+        Either::Right(read) => LinesSource::Reader {
+            read,
+            buf: Vec::new(),
+            pos: 0,
+            eof: false,
+        },
+    };
+    let native: Option<bool> = opt_param!(opts, "native")?;
+    let native = native.unwrap_or(false);
+    let options = DecodeOptions::new(opts.as_ref())?;
+    if native && options.reviver.is_some() {
+        return Err(LuaError::runtime("`reviver` is not supported for native records"));
+    }
+    let iter_ud = AnyUserData::wrap(LuaJsonLinesIter {
+        source,
+        line: 0,
+        native,
+        options,
+    });
+    (LuaJsonLinesIter::lua_iterator(lua)?, iter_ud).into_lua_multi(lua)
+}
+
+/// Encodes a Lua value into a JSON string.
+///
+/// The optional `opts` table can contain:
+/// - `pretty` (boolean): If true, pretty formats the JSON string with 2-space indentation and sorted
+///   keys. Default is false.
+/// - `relaxed` (boolean): If true, skip recursive tables and unsupported types. Default is false.
+/// - `sort_keys` (boolean): If true, sort object keys. Default is false (true in pretty mode).
+/// - `indent` (number or string): Number of spaces or a string to indent nested values with.
+/// - `ascii_only` (boolean): If true, escape non-ASCII characters as `\uXXXX`. Default is false.
+/// - `escape_slash` (boolean): If true, escape forward slashes as `\/`. Default is false.
+/// - `float_precision` (number): Number of digits to write after the decimal point for floats.
+/// - `empty_table_as_array` (boolean): If true, encode empty tables as `[]`. Default is false.
+/// - `replacer` (function): A `replacer(key, value)` function that is called for every value and
+///   returns the value to encode instead (or `nil` to skip it).
+///
+/// Tables and userdata with a `__tojson` (or `__serialize`) metamethod are encoded as the value
+/// returned by the metamethod.
+pub fn encode(lua: &Lua, (value, opts): (Value, Option<Table>)) -> Result<StdResult<String, String>> {
+    let replacer: Option<Function> = opt_param!(opts, "replacer")?;
+    let relaxed = opt_param!(opts, "relaxed")?.unwrap_or(false);
+    let pretty = opt_param!(opts, "pretty")?.unwrap_or(false);
+    let sort_keys = opt_param!(opts, "sort_keys")?.unwrap_or(pretty);
+    let empty_table_as_array = opt_param!(opts, "empty_table_as_array")?.unwrap_or(false);
+    let encoder = hooks::Encoder::new(lua, ENCODE_METAMETHODS, replacer.as_ref())
+        .exact_numbers(true)
+        .relaxed(relaxed)
+        .sort_keys(sort_keys)
+        .empty_tables_as_array(empty_table_as_array);
+    let value = encoder.wrap(value)?;
+
+    let mut formatter = EncodeFormatter::default();
+    formatter.indent = match opt_param!(opts, "indent")? {
+        Some(Value::String(indent)) => Some(indent.as_bytes().to_vec()),
+        Some(Value::Integer(width)) if width >= 0 => Some(vec![b' '; width as usize]),
+        Some(Value::Number(width)) if width >= 0.0 && width.fract() == 0.0 => {
+            Some(vec![b' '; width as usize])
+        }
+        Some(_) => {
+            return Err(LuaError::runtime(
+                "`indent` must be a string or a non-negative integer",
+            ));
+        }
+        None if pretty => Some(b"  ".to_vec()),
+        None => None,
+    };
+    formatter.ascii_only = opt_param!(opts, "ascii_only")?.unwrap_or(false);
+    formatter.escape_slash = opt_param!(opts, "escape_slash")?.unwrap_or(false);
+    formatter.float_precision = opt_param!(opts, "float_precision")?;
+    if formatter.is_default() {
+        return encoder.finish(serde_json::to_string(&value).map_err(|e| e.to_string()));
+    }
+
+    let mut output = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    let result = value.serialize(&mut serializer);
+    lua_try!(encoder.finish(result)?);
+    Ok(String::from_utf8(output).map_err(|e| e.to_string()))
+}
+
+/// Encodes a sequence of Lua values into newline-delimited JSON (JSON Lines).
+///
+/// Every value is written on its own line, and the output ends with a newline.
+///
+/// The optional `opts` table can contain:
+/// - `relaxed` (boolean): If true, skip recursive tables and unsupported types. Default is false.
+/// - `replacer` (function): A `replacer(key, value)` function, see [`encode`].
+pub fn encode_lines(lua: &Lua, (seq, opts): (Table, Option<Table>)) -> Result<StdResult<String, String>> {
+    let relaxed = opt_param!(opts, "relaxed")?.unwrap_or(false);
+    let replacer: Option<Function> = opt_param!(opts, "replacer")?;
+
+    let mut output = String::new();
+    for (i, value) in seq.sequence_values::<Value>().enumerate() {
+        let value = lua_try!(value.map_err(|err| format!("item {}: {err}", i + 1)));
+        let encoder = hooks::Encoder::new(lua, ENCODE_METAMETHODS, replacer.as_ref())
+            .exact_numbers(true)
+            .relaxed(relaxed);
+        let value = encoder.wrap(value)?;
+        let line = encoder.finish(serde_json::to_string(&value))?;
+        let line = lua_try!(line.map_err(|err| format!("item {}: {err}", i + 1)));
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(Ok(output))
+}
+
+/// Converts a Lua value into a JSON value, keeping the exact text of `Number` userdata.
+fn lua_to_json(lua: &Lua, value: Value) -> Result<JsonValue> {
+    let encoder = hooks::Encoder::new(lua, &[], None).exact_numbers(true);
+    let value = encoder.wrap(value)?;
+    let text = encoder
+        .finish(serde_json::to_vec(&value))?
+        .map_err(LuaError::external)?;
+    JsonValue::from_slice(&text, true).map_err(LuaError::external)
+}
+
+/// Serializes `Number` and native object userdata with the exact text of big numbers.
+///
+/// Returns the serializer back if the userdata is of another type.
+pub(crate) fn serialize_exact<S: Serializer>(
+    ud: &AnyUserData,
+    serializer: S,
+) -> StdResult<StdResult<S::Ok, S::Error>, S> {
+    if let Ok(num) = ud.borrow::<Number>() {
+        return Ok(num.serialize_exact(serializer));
+    }
+    if let Ok(obj) = ud.borrow::<JsonObject>() {
+        return Ok(match obj.current() {
+            Ok(current) => current.exact().serialize(serializer),
+            Err(err) => Err(ser::Error::custom(err)),
+        });
+    }
+    Err(serializer)
+}
+
+/// Converts a JSON number without big numbers into a Lua integer or float.
+fn number_to_lua(n: &serde_json::Number) -> Value {
+    match n.as_i64() {
+        Some(i) => Value::Integer(i as _),
+        None => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+/// Converts a Lua table or native object into a JSON value.
+///
+/// If the input was a native object, also returns whether it keeps big numbers.
+fn json_from_lua(lua: &Lua, value: Value) -> Result<(JsonValue, Option<bool>)> {
+    let native = match &value {
+        Value::UserData(ud) => ud.borrow::<JsonObject>().ok().map(|obj| obj.big_numbers),
+        _ => None,
+    };
+    Ok((lua_to_json(lua, value)?, native))
+}
+
+/// Converts a JSON value back into a Lua table or a native object.
+fn json_to_lua(lua: &Lua, value: JsonValue, native: Option<bool>) -> Result<Value> {
+    match native {
+        Some(big_numbers) => JsonObject::new(value, big_numbers).into_lua(lua),
+        // Numbers that Lua cannot represent can only come from `Number` userdata
+        None => json_value_to_lua(lua, &value, SerializeOptions::new(), true),
+    }
+}
+
+/// Applies a JSON Patch (RFC 6902) to a document and returns the patched document.
+///
+/// Both the document and the list of operations can be Lua tables or native objects.
+/// The original document is not modified, and the result has the same form as the input.
+pub fn patch(lua: &Lua, (doc, ops): (Value, Value)) -> Result<StdResult<Value, String>> {
+    let (doc, native) = lua_try!(json_from_lua(lua, doc));
+    let ops = match ops {
+        // An empty table is an empty list of operations
+        Value::Table(table) if table.is_empty() => JsonValue::Array(Vec::new()),
+        ops => lua_try!(json_from_lua(lua, ops)).0,
+    };
+    let mut numbers = BigNumbers::new(&[&doc, &ops]);
+    let mut doc = numbers.hide(&doc);
+    let ops: json_patch::Patch = lua_try!(serde_json::from_value(numbers.hide(&ops)));
+    lua_try!(json_patch::patch(&mut doc, &ops));
+    Ok(Ok(json_to_lua(lua, numbers.restore(doc), native)?))
+}
+
+/// Applies a JSON Merge Patch (RFC 7386) to a document and returns the merged document.
+///
+/// Both the document and the patch can be Lua tables or native objects.
+/// The original document is not modified, and the result has the same form as the input.
+pub fn merge_patch(lua: &Lua, (doc, patch): (Value, Value)) -> Result<StdResult<Value, String>> {
+    let (doc, native) = lua_try!(json_from_lua(lua, doc));
+    let (patch, _) = lua_try!(json_from_lua(lua, patch));
+    let mut numbers = BigNumbers::new(&[&doc, &patch]);
+    let mut doc = numbers.hide(&doc);
+    json_patch::merge(&mut doc, &numbers.hide(&patch));
+    Ok(Ok(json_to_lua(lua, numbers.restore(doc), native)?))
+}
+
+/// Computes a JSON Patch (RFC 6902) that transforms the document `a` into `b`.
+///
+/// The returned patch is a Lua table that can be passed to [`patch`].
+pub fn diff(lua: &Lua, (a, b): (Value, Value)) -> Result<StdResult<Value, String>> {
+    let (a, _) = lua_try!(json_from_lua(lua, a));
+    let (b, _) = lua_try!(json_from_lua(lua, b));
+    let mut numbers = BigNumbers::new(&[&a, &b]);
+    let (a, b) = (numbers.hide(&a), numbers.hide(&b));
+    let patch = numbers.restore(lua_try!(serde_json::to_value(json_patch::diff(&a, &b))));
+    Ok(Ok(json_value_to_lua(lua, &patch, SerializeOptions::new(), true)?))
+}
+
+/// A loader for the `json` module.
+fn loader(lua: &Lua) -> Result<Table> {
+    let t = lua.create_table()?;
+    t.set("decode", lua.create_function(decode)?)?;
+    t.set("decode_native", lua.create_function(decode_native)?)?;
+    t.set("encode", lua.create_function(encode)?)?;
+    t.set("lines", lua.create_function(lines)?)?;
+    t.set("encode_lines", lua.create_function(encode_lines)?)?;
+    t.set("patch", lua.create_function(patch)?)?;
+    t.set("merge_patch", lua.create_function(merge_patch)?)?;
+    t.set("diff", lua.create_function(diff)?)?;
+    t.set("Number", lua.create_proxy::<Number>()?)?;
+    #[cfg(feature = "json-schema")]
+    t.set("schema", lua.create_function(schema::compile)?)?;
+    Ok(t)
+}
+
+/// Registers the `json` module in the given Lua state.
+pub fn register(lua: &Lua, name: Option<&str>) -> Result<Table> {
+    let name = name.unwrap_or("@json");
+    let value = loader(lua)?;
+    lua.register_module(name, &value)?;
+    Ok(value)
+}
+
+mod dialect;
+mod format;
+mod number;
+mod value;
+
+#[cfg(feature = "json-schema")]
+mod schema;
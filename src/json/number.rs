@@ -0,0 +1,201 @@
+use std::fmt;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use mlua::{AnyUserData, Error as LuaError, Lua, MetaMethod, Result, UserData, UserDataMethods, Value};
+use serde::{Serialize, Serializer, ser};
+use serde_json::value::RawValue;
+
+/// An arbitrary-precision number that keeps its original JSON text.
+#[derive(Clone, Debug)]
+pub(crate) struct Number(String);
+
+impl Number {
+    /// Creates a new `Number` from the JSON text of a number.
+    pub(crate) fn new(text: &str) -> Option<Self> {
+        is_json_number(text).then(|| Number(text.to_owned()))
+    }
+
+    /// Returns true if the JSON number `text` can be represented by a Lua number without loss.
+    pub(crate) fn is_exact(text: &str) -> bool {
+        if text.parse::<i64>().is_ok() {
+            return true;
+        }
+        // Integers that do not fit into 64 bits and floats that cannot be represented exactly
+        // are kept as big numbers
+        if !text.contains(['.', 'e', 'E']) {
+            return false;
+        }
+        let shortest = text.parse::<f64>().unwrap_or(f64::NAN).to_string();
+        shortest == text || BigDecimal::from_str(&shortest).ok() == BigDecimal::from_str(text).ok()
+    }
+
+    /// Converts this number into a Lua value.
+    ///
+    /// The number is returned as `Number` userdata if `big_numbers` is set, and is rounded to the
+    /// nearest float otherwise.
+    pub(crate) fn to_lua(&self, lua: &Lua, big_numbers: bool) -> Result<Value> {
+        match big_numbers {
+            true => Ok(Value::UserData(self.clone().into_userdata(lua)?)),
+            false => Ok(Value::Number(self.to_f64())),
+        }
+    }
+
+    /// Serializes this number with its exact text.
+    ///
+    /// Only `serde_json` serializers support it.
+    pub(crate) fn serialize_exact<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        let raw = RawValue::from_string(self.0.clone()).map_err(ser::Error::custom)?;
+        raw.serialize(serializer)
+    }
+
+    fn from_decimal(decimal: BigDecimal) -> Result<Self> {
+        let text = decimal.to_string();
+        Number::new(&text).ok_or_else(|| LuaError::runtime(format!("invalid number '{text}'")))
+    }
+
+    /// Returns the nearest float to this number.
+    pub(crate) fn to_f64(&self) -> f64 {
+        self.0.parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    /// Creates a serializable userdata from this number.
+    pub(crate) fn into_userdata(self, lua: &Lua) -> Result<AnyUserData> {
+        lua.create_ser_userdata(self)
+    }
+
+    fn to_decimal(&self) -> BigDecimal {
+        BigDecimal::from_str(&self.0).expect("valid JSON number")
+    }
+
+    /// Converts this number into a Lua integer or float (possibly losing precision).
+    fn to_lua_number(&self) -> Value {
+        match self.0.parse::<i64>() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::Number(self.to_f64()),
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 || self.to_decimal() == other.to_decimal()
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Serializes the number as a plain 64-bit integer or float, which all formats support.
+///
+/// See [`Number::serialize_exact`] for the exact form.
+impl Serialize for Number {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        if let Ok(i) = self.0.parse::<i64>() {
+            return serializer.serialize_i64(i);
+        }
+        if let Ok(u) = self.0.parse::<u64>() {
+            return serializer.serialize_u64(u);
+        }
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// Checks that the text is a valid JSON number (RFC 8259).
+fn is_json_number(text: &str) -> bool {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = text.strip_prefix('-').unwrap_or(text);
+    let rest = match digits(rest) {
+        0 => return false,
+        n if n > 1 && rest.starts_with('0') => return false,
+        n => &rest[n..],
+    };
+    let rest = match rest.strip_prefix('.') {
+        Some(frac) if digits(frac) == 0 => return false,
+        Some(frac) => &frac[digits(frac)..],
+        None => rest,
+    };
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exp) => {
+            let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+            digits(exp) > 0 && digits(exp) == exp.len()
+        }
+        None => rest.is_empty(),
+    }
+}
+
+/// Converts a Lua number, numeric string or `Number` into a decimal.
+fn to_decimal(value: &Value) -> Result<BigDecimal> {
+    match value {
+        Value::Integer(i) => Ok(BigDecimal::from(*i)),
+        Value::Number(n) if n.is_finite() => Ok(BigDecimal::from_str(&n.to_string()).expect("finite float")),
+        Value::String(s) => match Number::new(&s.to_str()?) {
+            Some(num) => Ok(num.to_decimal()),
+            None => Err(LuaError::runtime(format!("invalid number '{}'", s.display()))),
+        },
+        Value::UserData(ud) if ud.is::<Number>() => Ok(ud.borrow::<Number>()?.to_decimal()),
+        _ => Err(LuaError::runtime(format!(
+            "cannot convert {} to a number",
+            value.type_name()
+        ))),
+    }
+}
+
+impl UserData for Number {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_function("new", |lua, value: Value| {
+            let num = match &value {
+                Value::String(s) => Number::new(&s.to_str()?),
+                _ => to_decimal(&value).and_then(Number::from_decimal).ok(),
+            };
+            match num {
+                Some(num) => Ok(Ok(num.into_userdata(lua)?)),
+                None => Ok(Err(format!("invalid number '{}'", value.to_string()?))),
+            }
+        });
+
+        registry.add_method("to_number", |_, this, ()| Ok(this.to_lua_number()));
+
+        registry.add_meta_function(MetaMethod::Add, |lua, (a, b): (Value, Value)| {
+            Number::from_decimal(to_decimal(&a)? + to_decimal(&b)?)?.into_userdata(lua)
+        });
+
+        registry.add_meta_function(MetaMethod::Sub, |lua, (a, b): (Value, Value)| {
+            Number::from_decimal(to_decimal(&a)? - to_decimal(&b)?)?.into_userdata(lua)
+        });
+
+        registry.add_meta_function(MetaMethod::Mul, |lua, (a, b): (Value, Value)| {
+            Number::from_decimal(to_decimal(&a)? * to_decimal(&b)?)?.into_userdata(lua)
+        });
+
+        registry.add_meta_function(MetaMethod::Div, |lua, (a, b): (Value, Value)| {
+            let divisor = to_decimal(&b)?;
+            if divisor.is_zero() {
+                return Err(LuaError::runtime("division by zero"));
+            }
+            Number::from_decimal(to_decimal(&a)? / divisor)?.into_userdata(lua)
+        });
+
+        registry.add_meta_method(MetaMethod::Unm, |lua, this, ()| {
+            Number::from_decimal(-this.to_decimal())?.into_userdata(lua)
+        });
+
+        registry.add_meta_function(MetaMethod::Eq, |_, (a, b): (Value, Value)| {
+            Ok(to_decimal(&a)? == to_decimal(&b)?)
+        });
+
+        registry.add_meta_function(MetaMethod::Lt, |_, (a, b): (Value, Value)| {
+            Ok(to_decimal(&a)? < to_decimal(&b)?)
+        });
+
+        registry.add_meta_function(MetaMethod::Le, |_, (a, b): (Value, Value)| {
+            Ok(to_decimal(&a)? <= to_decimal(&b)?)
+        });
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.to_string()));
+    }
+}
@@ -0,0 +1,86 @@
+use std::result::Result as StdResult;
+use std::sync::{Arc, LazyLock};
+
+use jsonschema::Draft;
+use mlua::{Either, Lua, Result, String as LuaString, Table, UserData, UserDataMethods, Value};
+use quick_cache::sync::Cache;
+
+// A reasonable cache size for compiled schemas. This can be adjusted as needed.
+const SCHEMA_CACHE_SIZE: usize = 64;
+
+/// A compiled JSON Schema validator.
+#[derive(Clone)]
+pub(crate) struct Schema(Arc<jsonschema::Validator>);
+
+// Global cache for compiled schemas shared across all Lua states.
+static CACHE: LazyLock<Cache<(String, Option<Draft>), Schema>> =
+    LazyLock::new(|| Cache::new(SCHEMA_CACHE_SIZE));
+
+impl Schema {
+    /// Compiles a new cached schema or retrieves it from the cache if it already exists.
+    ///
+    /// If `draft` is not set, it is detected from the `$schema` keyword (defaults to 2020-12).
+    fn new(schema: &serde_json::Value, draft: Option<Draft>) -> StdResult<Self, String> {
+        let key = (schema.to_string(), draft);
+        CACHE.get_or_insert_with(&key, || {
+            let mut options = jsonschema::options();
+            if let Some(draft) = draft {
+                options = options.with_draft(draft);
+            }
+            let validator = options.build(schema).map_err(|err| err.to_string())?;
+            Ok(Schema(Arc::new(validator)))
+        })
+    }
+}
+
+impl UserData for Schema {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("is_valid", |lua, this, value: Value| {
+            let instance = super::lua_to_json(lua, value)?.to_serde();
+            Ok(this.0.is_valid(&instance))
+        });
+
+        // Returns a list of validation errors (empty if the value is valid).
+        registry.add_method("validate", |lua, this, value: Value| {
+            let instance = super::lua_to_json(lua, value)?.to_serde();
+            let errors = lua.create_table()?;
+            for err in this.0.iter_errors(&instance) {
+                let item = lua.create_table_with_capacity(0, 3)?;
+                item.raw_set("message", err.to_string())?;
+                item.raw_set("instance_path", err.instance_path().as_str())?;
+                item.raw_set("schema_path", err.schema_path().as_str())?;
+                errors.raw_push(item)?;
+            }
+            Ok(errors)
+        });
+    }
+}
+
+/// Compiles a JSON Schema into a reusable validator.
+///
+/// The schema can be a JSON string, a Lua table or a native object.
+///
+/// The optional `opts` table can contain:
+/// - `draft` (string): The draft to use: "4", "6", "7", "2019-09" or "2020-12". By default, the draft is
+///   detected from the `$schema` keyword.
+pub fn compile(
+    lua: &Lua,
+    (schema, opts): (Either<LuaString, Value>, Option<Table>),
+) -> Result<StdResult<Schema, String>> {
+    let schema: serde_json::Value = match schema {
+        Either::Left(s) => lua_try!(serde_json::from_slice(&s.as_bytes())),
+        Either::Right(value) => lua_try!(super::lua_to_json(lua, value)).to_serde(),
+    };
+    let draft = match opt_param!(opts, "draft")? {
+        None => None,
+        Some::<String>(draft) => Some(match &*draft {
+            "4" => Draft::Draft4,
+            "6" => Draft::Draft6,
+            "7" => Draft::Draft7,
+            "2019-09" => Draft::Draft201909,
+            "2020-12" => Draft::Draft202012,
+            _ => return Ok(Err(format!("unsupported draft '{draft}'"))),
+        }),
+    };
+    Ok(Ok(lua_try!(Schema::new(&schema, draft))))
+}
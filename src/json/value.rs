@@ -0,0 +1,373 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::result::Result as StdResult;
+use std::vec;
+
+use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::number::Number;
+
+/// A JSON value.
+///
+/// Unlike `serde_json::Value`, it can keep numbers that Lua cannot represent without loss as
+/// [`Number`]s with their original text.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    BigNumber(Number),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Map),
+}
+
+/// A JSON object, with keys in sorted order (as in `serde_json`).
+pub(crate) type Map = BTreeMap<String, JsonValue>;
+
+impl JsonValue {
+    /// Parses a JSON string or bytes.
+    ///
+    /// If `big_numbers` is set, numbers that Lua cannot represent without loss are kept as
+    /// [`JsonValue::BigNumber`].
+    pub(crate) fn from_slice(data: &[u8], big_numbers: bool) -> serde_json::Result<Self> {
+        let numbers = big_numbers.then(|| RefCell::new(scan_numbers(data).into_iter()));
+        let mut deserializer = serde_json::Deserializer::from_slice(data);
+        let value = ValueSeed {
+            numbers: numbers.as_ref(),
+        }
+        .deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(value)
+    }
+
+    pub(crate) fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    pub(crate) fn is_array(&self) -> bool {
+        matches!(self, JsonValue::Array(_))
+    }
+
+    pub(crate) fn is_object(&self) -> bool {
+        matches!(self, JsonValue::Object(_))
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(vec) => Some(vec),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array_mut(&mut self) -> Option<&mut Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(vec) => Some(vec),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_object(&self) -> Option<&Map> {
+        match self {
+            JsonValue::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_object_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            JsonValue::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Returns a serializable form of this value that writes big numbers with their exact text.
+    ///
+    /// Only `serde_json` serializers support it, other formats must use the plain [`Serialize`]
+    /// implementation that writes big numbers as 64-bit floats.
+    pub(crate) fn exact(&self) -> Exact<'_> {
+        Exact(self)
+    }
+
+    /// Converts this value into a `serde_json::Value`, rounding big numbers to the nearest float.
+    pub(crate) fn to_serde(&self) -> serde_json::Value {
+        match self {
+            JsonValue::Null => serde_json::Value::Null,
+            JsonValue::Bool(b) => serde_json::Value::Bool(*b),
+            JsonValue::Number(n) => serde_json::Value::Number(n.clone()),
+            JsonValue::BigNumber(n) => serde_json::Value::from(n.to_f64()),
+            JsonValue::String(s) => serde_json::Value::String(s.clone()),
+            JsonValue::Array(vec) => serde_json::Value::Array(vec.iter().map(Self::to_serde).collect()),
+            JsonValue::Object(map) => {
+                let map = map.iter().map(|(k, v)| (k.clone(), v.to_serde()));
+                serde_json::Value::Object(map.collect())
+            }
+        }
+    }
+}
+
+impl From<serde_json::Value> for JsonValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => JsonValue::Null,
+            serde_json::Value::Bool(b) => JsonValue::Bool(b),
+            serde_json::Value::Number(n) => JsonValue::Number(n),
+            serde_json::Value::String(s) => JsonValue::String(s),
+            serde_json::Value::Array(vec) => JsonValue::Array(vec.into_iter().map(Self::from).collect()),
+            serde_json::Value::Object(map) => {
+                JsonValue::Object(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Replaces big numbers with unique strings, so values can be processed as `serde_json::Value`s
+/// without losing precision.
+///
+/// The strings start with a prefix that is not used by any string of the values.
+pub(crate) struct BigNumbers {
+    prefix: String,
+    numbers: Vec<Number>,
+}
+
+impl BigNumbers {
+    pub(crate) fn new(values: &[&JsonValue]) -> Self {
+        fn uses_prefix(value: &JsonValue, prefix: &str) -> bool {
+            match value {
+                JsonValue::String(s) => s.starts_with(prefix),
+                JsonValue::Array(vec) => vec.iter().any(|v| uses_prefix(v, prefix)),
+                JsonValue::Object(map) => map.values().any(|v| uses_prefix(v, prefix)),
+                _ => false,
+            }
+        }
+
+        let mut prefix = String::from("\0big-number:");
+        while values.iter().any(|v| uses_prefix(v, &prefix)) {
+            prefix.insert(0, '\0');
+        }
+        BigNumbers {
+            prefix,
+            numbers: Vec::new(),
+        }
+    }
+
+    /// Converts a value into a `serde_json::Value`, replacing big numbers with strings.
+    ///
+    /// Equal numbers get the same string, so they stay equal.
+    pub(crate) fn hide(&mut self, value: &JsonValue) -> serde_json::Value {
+        match value {
+            JsonValue::BigNumber(n) => {
+                let index = match self.numbers.iter().position(|x| x == n) {
+                    Some(index) => index,
+                    None => {
+                        self.numbers.push(n.clone());
+                        self.numbers.len() - 1
+                    }
+                };
+                serde_json::Value::String(format!("{}{index}", self.prefix))
+            }
+            JsonValue::Array(vec) => serde_json::Value::Array(vec.iter().map(|v| self.hide(v)).collect()),
+            JsonValue::Object(map) => {
+                let map = map.iter().map(|(k, v)| (k.clone(), self.hide(v)));
+                serde_json::Value::Object(map.collect())
+            }
+            value => value.to_serde(),
+        }
+    }
+
+    /// Converts a `serde_json::Value` back, restoring the big numbers.
+    pub(crate) fn restore(&self, value: serde_json::Value) -> JsonValue {
+        match value {
+            serde_json::Value::String(s) => {
+                let number = (s.strip_prefix(&self.prefix))
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| self.numbers.get(index));
+                match number {
+                    Some(n) => JsonValue::BigNumber(n.clone()),
+                    None => JsonValue::String(s),
+                }
+            }
+            serde_json::Value::Array(vec) => {
+                JsonValue::Array(vec.into_iter().map(|v| self.restore(v)).collect())
+            }
+            serde_json::Value::Object(map) => {
+                JsonValue::Object(map.into_iter().map(|(k, v)| (k, self.restore(v))).collect())
+            }
+            value => value.into(),
+        }
+    }
+}
+
+/// Returns the text of all numbers in the JSON input, in order.
+fn scan_numbers(data: &[u8]) -> Vec<&str> {
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'"' => {
+                i += 1;
+                while i < data.len() && data[i] != b'"' {
+                    if data[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = i;
+                while i < data.len() && matches!(data[i], b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') {
+                    i += 1;
+                }
+                // Number characters are ASCII
+                numbers.push(str::from_utf8(&data[start..i]).unwrap_or_default());
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    numbers
+}
+
+/// Builds a [`JsonValue`], taking the text of numbers from the scanned input if available.
+#[derive(Clone, Copy)]
+struct ValueSeed<'a, 'b> {
+    numbers: Option<&'b RefCell<vec::IntoIter<&'a str>>>,
+}
+
+impl ValueSeed<'_, '_> {
+    fn number(self, n: serde_json::Number) -> JsonValue {
+        let text = self.numbers.and_then(|numbers| numbers.borrow_mut().next());
+        match text {
+            Some(text) if !Number::is_exact(text) => match Number::new(text) {
+                Some(n) => JsonValue::BigNumber(n),
+                None => JsonValue::Number(n),
+            },
+            _ => JsonValue::Number(n),
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed<'_, '_> {
+    type Value = JsonValue;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> StdResult<JsonValue, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed<'_, '_> {
+    type Value = JsonValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> StdResult<JsonValue, E> {
+        Ok(JsonValue::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> StdResult<JsonValue, E> {
+        Ok(self.number(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> StdResult<JsonValue, E> {
+        Ok(self.number(v.into()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> StdResult<JsonValue, E> {
+        Ok(serde_json::Number::from_f64(v).map_or(JsonValue::Null, |n| self.number(n)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> StdResult<JsonValue, E> {
+        Ok(JsonValue::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> StdResult<JsonValue, E> {
+        Ok(JsonValue::String(v))
+    }
+
+    fn visit_none<E>(self) -> StdResult<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> StdResult<JsonValue, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> StdResult<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> StdResult<JsonValue, A::Error> {
+        let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element_seed(self)? {
+            vec.push(value);
+        }
+        Ok(JsonValue::Array(vec))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> StdResult<JsonValue, A::Error> {
+        let mut map = Map::new();
+        while let Some(key) = access.next_key::<String>()? {
+            let value = access.next_value_seed(self)?;
+            map.insert(key, value);
+        }
+        Ok(JsonValue::Object(map))
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> StdResult<Self, D::Error> {
+        ValueSeed { numbers: None }.deserialize(deserializer)
+    }
+}
+
+impl Serialize for JsonValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        serialize_value(self, false, serializer)
+    }
+}
+
+/// A [`JsonValue`] that is serialized with the exact text of big numbers.
+pub(crate) struct Exact<'a>(&'a JsonValue);
+
+impl Serialize for Exact<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        serialize_value(self.0, true, serializer)
+    }
+}
+
+fn serialize_value<S: Serializer>(
+    value: &JsonValue,
+    exact: bool,
+    serializer: S,
+) -> StdResult<S::Ok, S::Error> {
+    match value {
+        JsonValue::Null => serializer.serialize_unit(),
+        JsonValue::Bool(b) => serializer.serialize_bool(*b),
+        JsonValue::Number(n) => n.serialize(serializer),
+        JsonValue::BigNumber(n) if exact => n.serialize_exact(serializer),
+        JsonValue::BigNumber(n) => n.serialize(serializer),
+        JsonValue::String(s) => serializer.serialize_str(s),
+        JsonValue::Array(vec) if exact => {
+            let mut seq = serializer.serialize_seq(Some(vec.len()))?;
+            for value in vec {
+                seq.serialize_element(&Exact(value))?;
+            }
+            seq.end()
+        }
+        JsonValue::Array(vec) => vec.serialize(serializer),
+        JsonValue::Object(map) if exact => {
+            let mut ser = serializer.serialize_map(Some(map.len()))?;
+            for (key, value) in map {
+                ser.serialize_entry(key, &Exact(value))?;
+            }
+            ser.end()
+        }
+        JsonValue::Object(map) => map.serialize(serializer),
+    }
+}
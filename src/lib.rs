@@ -7,6 +7,9 @@ mod macros;
 mod types;
 mod util;
 
+#[cfg(any(feature = "json", feature = "yaml"))]
+mod hooks;
+
 pub(crate) mod terminal;
 
 pub mod assertions;
@@ -15,6 +18,10 @@ pub mod env;
 pub mod testing;
 pub mod time;
 
+#[cfg(any(feature = "json", feature = "yaml"))]
+pub mod error;
+#[cfg(feature = "glob")]
+pub mod glob;
 #[cfg(feature = "json")]
 pub mod json;
 #[cfg(feature = "regex")]
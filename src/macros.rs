@@ -7,6 +7,13 @@ macro_rules! lua_try {
             Err(err) => return Ok(Err(format!("{err:#}"))),
         }
     };
+
+    ($result:expr, $map_err:expr) => {
+        match $result {
+            Ok(ok) => ok,
+            Err(err) => return Ok(Err($map_err(err))),
+        }
+    };
 }
 
 macro_rules! defer {
@@ -0,0 +1,217 @@
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+
+use quick_cache::sync::Cache;
+use quick_cache::{DefaultHashBuilder, Lifecycle, UnitWeighter};
+
+#[cfg(feature = "regex-fancy")]
+use super::FancyRegex;
+use super::{Regex, RegexOptions, RegexSet};
+
+// A reasonable cache size for regexes. This can be adjusted as needed.
+const REGEX_CACHE_SIZE: usize = 256;
+
+/// Configuration of the `regex` module.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Where compiled regexes are cached.
+    pub cache_mode: CacheMode,
+    /// Maximum number of regexes kept in a per-state cache.
+    ///
+    /// A size of 0 disables the cache, like [`CacheMode::Disabled`]. The global cache always holds
+    /// up to 256 regexes.
+    pub cache_size: usize,
+    /// Upper bound of the compiled regex size limit (in bytes).
+    ///
+    /// Scripts can request a lower limit using the `size_limit` option, but not a higher one.
+    pub size_limit: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cache_mode: CacheMode::Global,
+            cache_size: REGEX_CACHE_SIZE,
+            size_limit: None,
+        }
+    }
+}
+
+/// Where compiled regexes are cached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// A cache shared across all Lua states.
+    #[default]
+    Global,
+    /// A cache private to the Lua state.
+    PerState,
+    /// Regexes are compiled every time.
+    Disabled,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Regex(String, RegexOptions),
+    Set(Vec<String>, RegexOptions),
+    #[cfg(feature = "regex-fancy")]
+    Fancy(String, RegexOptions),
+}
+
+#[derive(Clone)]
+enum Compiled {
+    Regex(Regex),
+    Set(RegexSet),
+    #[cfg(feature = "regex-fancy")]
+    Fancy(FancyRegex),
+}
+
+/// A cache of compiled regexes that keeps usage statistics.
+pub(crate) struct RegexCache {
+    cache: Option<Cache<CacheKey, Compiled, UnitWeighter, DefaultHashBuilder, EvictionCounter>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: Arc<AtomicU64>,
+}
+
+// Global cache for regexes shared across all Lua states.
+static GLOBAL_CACHE: LazyLock<Arc<RegexCache>> =
+    LazyLock::new(|| Arc::new(RegexCache::new(REGEX_CACHE_SIZE)));
+
+impl RegexCache {
+    fn new(size: usize) -> Self {
+        let evictions = Arc::new(AtomicU64::new(0));
+        let lifecycle = EvictionCounter(evictions.clone());
+        let cache = Cache::with(
+            size,
+            size as u64,
+            UnitWeighter,
+            DefaultHashBuilder::default(),
+            lifecycle,
+        );
+        RegexCache {
+            cache: Some(cache),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions,
+        }
+    }
+
+    /// Returns the cache for the given configuration.
+    pub(crate) fn from_config(config: &Config) -> Arc<Self> {
+        match config.cache_mode {
+            CacheMode::Global => GLOBAL_CACHE.clone(),
+            CacheMode::PerState if config.cache_size > 0 => Arc::new(RegexCache::new(config.cache_size)),
+            CacheMode::PerState | CacheMode::Disabled => Arc::new(RegexCache {
+                cache: None,
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                evictions: Arc::new(AtomicU64::new(0)),
+            }),
+        }
+    }
+
+    /// Returns the global cache.
+    pub(crate) fn global() -> Arc<Self> {
+        GLOBAL_CACHE.clone()
+    }
+
+    /// Retrieves a regex from the cache or compiles and caches it.
+    pub(crate) fn get_or_compile(&self, re: &str, opts: &RegexOptions) -> StdResult<Regex, regex::Error> {
+        let key = CacheKey::Regex(re.to_string(), opts.clone());
+        match self.get_or_insert(key, || Ok(Compiled::Regex(Regex(opts.build(re)?))))? {
+            Compiled::Regex(regex) => Ok(regex),
+            _ => unreachable!("regex cache key maps to another kind of regex"),
+        }
+    }
+
+    /// Retrieves a regex set from the cache or compiles and caches it.
+    pub(crate) fn get_or_compile_set(
+        &self,
+        patterns: &[String],
+        opts: &RegexOptions,
+    ) -> StdResult<RegexSet, regex::Error> {
+        let key = CacheKey::Set(patterns.to_vec(), opts.clone());
+        let compile = || {
+            let set = opts.build_set(patterns)?;
+            Ok(Compiled::Set(RegexSet {
+                set,
+                opts: opts.clone(),
+            }))
+        };
+        match self.get_or_insert(key, compile)? {
+            Compiled::Set(set) => Ok(set),
+            _ => unreachable!("regex set cache key maps to another kind of regex"),
+        }
+    }
+
+    /// Retrieves a `fancy-regex` regex from the cache or compiles and caches it.
+    #[cfg(feature = "regex-fancy")]
+    pub(crate) fn get_or_compile_fancy(
+        &self,
+        re: &str,
+        opts: &RegexOptions,
+    ) -> StdResult<FancyRegex, fancy_regex::Error> {
+        let key = CacheKey::Fancy(re.to_string(), opts.clone());
+        let compile = || opts.build_fancy(re).map(|re| Compiled::Fancy(FancyRegex(re)));
+        match self.get_or_insert(key, compile)? {
+            Compiled::Fancy(regex) => Ok(regex),
+            _ => unreachable!("fancy regex cache key maps to another kind of regex"),
+        }
+    }
+
+    fn get_or_insert<E>(
+        &self,
+        key: CacheKey,
+        compile: impl FnOnce() -> StdResult<Compiled, E>,
+    ) -> StdResult<Compiled, E> {
+        let mut compiled = false;
+        let compile = || {
+            compiled = true;
+            compile()
+        };
+        let result = match &self.cache {
+            Some(cache) => cache.get_or_insert_with(&key, compile),
+            None => compile(),
+        };
+        match compiled {
+            true => self.misses.fetch_add(1, Ordering::Relaxed),
+            false => self.hits.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    /// Returns the cache statistics.
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            size: self.cache.as_ref().map_or(0, |cache| cache.len()),
+            capacity: self.cache.as_ref().map_or(0, |cache| cache.capacity()),
+        }
+    }
+}
+
+/// Usage statistics of a regex cache.
+pub(crate) struct CacheStats {
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) evictions: u64,
+    pub(crate) size: usize,
+    pub(crate) capacity: u64,
+}
+
+/// Cache lifecycle that counts evicted regexes.
+#[derive(Clone)]
+struct EvictionCounter(Arc<AtomicU64>);
+
+impl Lifecycle<CacheKey, Compiled> for EvictionCounter {
+    type RequestState = ();
+
+    fn begin_request(&self) -> Self::RequestState {}
+
+    fn on_evict(&self, _: &mut Self::RequestState, _: CacheKey, _: Compiled) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,315 @@
+use std::ops::{Deref, Range};
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use mlua::{
+    AnyUserData, Error, Function, IntoLuaMulti, Lua, Result, String as LuaString, UserData, UserDataMethods,
+    UserDataRefMut, Value, Variadic,
+};
+use ouroboros::self_referencing;
+
+use super::{
+    CaptureGroups, MatchesIter, ModuleState, RegexOptions, Replacement, find_start, push_replacement,
+    register_captures,
+};
+
+/// A compiled regular expression using the backtracking `fancy-regex` engine.
+///
+/// Unlike [`Regex`](super::Regex), it supports lookaround and backreferences, but matches only
+/// UTF-8 text.
+#[derive(Clone, Debug)]
+pub struct FancyRegex(pub(crate) fancy_regex::Regex);
+
+impl Deref for FancyRegex {
+    type Target = fancy_regex::Regex;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl RegexOptions {
+    /// Builds a `fancy-regex` regex. The `crlf` option is not supported.
+    pub(crate) fn build_fancy(&self, re: &str) -> StdResult<fancy_regex::Regex, fancy_regex::Error> {
+        let mut builder = fancy_regex::RegexBuilder::new(re);
+        builder
+            .case_insensitive(self.case_insensitive)
+            .multi_line(self.multi_line)
+            .dot_matches_new_line(self.dot_matches_new_line)
+            .unicode_mode(self.unicode)
+            .ignore_whitespace(self.ignore_whitespace);
+        if let Some(limit) = self.size_limit {
+            builder.delegate_size_limit(limit);
+        }
+        if let Some(limit) = self.dfa_size_limit {
+            builder.delegate_dfa_size_limit(limit);
+        }
+        builder.build()
+    }
+}
+
+impl FancyRegex {
+    /// Creates a new cached regex with the given options or retrieves it from the cache if it
+    /// already exists.
+    pub fn with_options(lua: &Lua, re: &str, opts: &RegexOptions) -> StdResult<Self, fancy_regex::Error> {
+        let opts = ModuleState::options(lua, opts);
+        ModuleState::cache(lua).get_or_compile_fancy(re, &opts)
+    }
+
+    /// Returns an iterator function over matches positions.
+    fn lua_find_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__regex_fancy_find_iterator") {
+            return Ok(f);
+        }
+
+        let f =
+            lua.create_function(
+                |lua, mut it: UserDataRefMut<FancyMatchesIter>| match it.next_range()? {
+                    Some(range) => (range.start + 1, range.end).into_lua_multi(lua),
+                    None => ().into_lua_multi(lua),
+                },
+            )?;
+        lua.set_named_registry_value("__regex_fancy_find_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Returns an iterator function over captures.
+    fn lua_captures_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__regex_fancy_captures_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<FancyMatchesIter>| {
+            match it.next_captures()? {
+                Some(caps) => lua.create_userdata(caps)?.into_lua_multi(lua),
+                None => ().into_lua_multi(lua),
+            }
+        })?;
+        lua.set_named_registry_value("__regex_fancy_captures_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Replaces at most `limit` matches in the text, returning the result and the number of
+    /// replaced matches.
+    fn lua_replacen(
+        &self,
+        lua: &Lua,
+        text: &str,
+        limit: usize,
+        rep: Replacement,
+    ) -> Result<(LuaString, usize)> {
+        if limit == 0 {
+            return Ok((lua.create_string(text)?, 0));
+        }
+        if let Replacement::Template(rep) = &rep {
+            let (rep, mut count) = (rep.to_str()?, 0);
+            let result = self.try_replacen(text, limit, |caps: &fancy_regex::Captures| {
+                count += 1;
+                let mut dst = String::new();
+                caps.expand(&rep, &mut dst);
+                dst
+            });
+            let result = result.map_err(Error::external)?;
+            return Ok((lua.create_string(&*result)?, count));
+        }
+
+        let shared_text: Arc<str> = text.into();
+        let mut output = Vec::with_capacity(text.len());
+        let (mut last_end, mut count) = (0, 0);
+        for m in self.find_iter(text).take(limit) {
+            let m = m.map_err(Error::external)?;
+            let value = match &rep {
+                Replacement::Function(f) => {
+                    match FancyCaptures::find(self, shared_text.clone(), m.start())? {
+                        Some(caps) => f.call::<Value>(caps)?,
+                        None => Value::Nil,
+                    }
+                }
+                Replacement::Table(table) => table.get::<Value>(m.as_str())?,
+                Replacement::Template(_) => unreachable!(),
+            };
+            output.extend_from_slice(&text.as_bytes()[last_end..m.start()]);
+            push_replacement(lua, &mut output, value, m.as_str().as_bytes())?;
+            last_end = m.end();
+            count += 1;
+        }
+        output.extend_from_slice(&text.as_bytes()[last_end..]);
+        Ok((lua.create_string(output)?, count))
+    }
+}
+
+impl UserData for FancyRegex {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("is_match", |_, this, text: LuaString| {
+            this.0.is_match(&text.to_str()?).map_err(Error::external)
+        });
+
+        // Returns the start and end positions of the first match starting from `init`.
+        registry.add_method("find", |_, this, (text, init): (LuaString, Option<i64>)| {
+            let text = text.to_str()?;
+            let Some(init) = find_start(text.len(), init) else {
+                return Ok(Variadic::new());
+            };
+            match this.find_from_pos(&text, char_boundary(&text, init)) {
+                Ok(Some(m)) => Ok(Variadic::from_iter([m.start() + 1, m.end()])),
+                Ok(None) => Ok(Variadic::new()),
+                Err(err) => Err(Error::external(err)),
+            }
+        });
+
+        // Returns an iterator over the start and end positions of all non-overlapping matches.
+        registry.add_method("find_iter", |lua, this, text: LuaString| {
+            let next = Self::lua_find_iterator(lua)?;
+            let iter_ud = AnyUserData::wrap(FancyMatchesIter::new(this, &*text.to_str()?));
+            (next, iter_ud).into_lua_multi(lua)
+        });
+
+        // Returns an iterator over the captures of all non-overlapping matches.
+        registry.add_method("captures_iter", |lua, this, text: LuaString| {
+            let next = Self::lua_captures_iterator(lua)?;
+            let iter_ud = AnyUserData::wrap(FancyMatchesIter::new(this, &*text.to_str()?));
+            (next, iter_ud).into_lua_multi(lua)
+        });
+
+        registry.add_method("match", |lua, this, text: LuaString| {
+            match FancyCaptures::find(this, (*text.to_str()?).into(), 0)? {
+                Some(caps) => Ok(Value::UserData(lua.create_userdata(caps)?)),
+                None => Ok(Value::Nil),
+            }
+        });
+
+        registry.add_method("split", |lua, this, text: LuaString| {
+            let text = text.to_str()?;
+            let parts = this.split(&text).collect::<StdResult<Vec<_>, _>>();
+            lua.create_sequence_from(parts.map_err(Error::external)?)
+        });
+
+        registry.add_method("splitn", |lua, this, (text, limit): (LuaString, usize)| {
+            let text = text.to_str()?;
+            let parts = this.splitn(&text, limit).collect::<StdResult<Vec<_>, _>>();
+            lua.create_sequence_from(parts.map_err(Error::external)?)
+        });
+
+        registry.add_method("replace", |lua, this, (text, rep): (LuaString, Replacement)| {
+            Ok(this.lua_replacen(lua, &text.to_str()?, 1, rep)?.0)
+        });
+
+        // Replaces all matches, returning the result and the number of replaced matches.
+        registry.add_method(
+            "replace_all",
+            |lua, this, (text, rep): (LuaString, Replacement)| {
+                this.lua_replacen(lua, &text.to_str()?, usize::MAX, rep)
+            },
+        );
+
+        // Replaces at most `limit` matches (none if `limit` is zero, as in `string.gsub`), returning
+        // the result and the number of replaced matches.
+        registry.add_method(
+            "replacen",
+            |lua, this, (text, limit, rep): (LuaString, usize, Replacement)| {
+                this.lua_replacen(lua, &text.to_str()?, limit, rep)
+            },
+        );
+
+        // Returns the names of the named capture groups.
+        registry.add_method("capture_names", |lua, this, ()| {
+            lua.create_sequence_from(this.capture_names().flatten())
+        });
+
+        // Returns the total number of capture groups (including the whole match).
+        registry.add_method("captures_len", |_, this, ()| Ok(this.captures_len()));
+    }
+}
+
+type FancyMatchesIter = MatchesIter<FancyRegex, str>;
+
+impl FancyMatchesIter {
+    /// Returns the range of the next match.
+    fn next_range(&mut self) -> Result<Option<Range<usize>>> {
+        self.next_with(|regex, text, pos| {
+            let m = regex.find_from_pos(text, char_boundary(text, pos));
+            Ok(m.map_err(Error::external)?.map(|m| (m.range(), m.range())))
+        })
+    }
+
+    /// Returns the captures of the next match.
+    fn next_captures(&mut self) -> Result<Option<FancyCaptures>> {
+        self.next_with(|regex, text, pos| {
+            let caps = FancyCaptures::find(regex, text.clone(), char_boundary(text, pos))?;
+            Ok(caps.map(|caps| {
+                let range = caps
+                    .borrow_caps()
+                    .get(0)
+                    .expect("match is always present")
+                    .range();
+                (caps, range)
+            }))
+        })
+    }
+}
+
+/// Returns the first character boundary at or after `pos`.
+///
+/// The `fancy-regex` engine can only start searching at a character boundary.
+fn char_boundary(text: &str, pos: usize) -> usize {
+    (pos..text.len())
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(text.len())
+}
+
+#[self_referencing]
+struct FancyCaptures {
+    regex: FancyRegex,
+    text: Arc<str>,
+
+    #[borrows(text)]
+    #[covariant]
+    caps: fancy_regex::Captures<'this>,
+}
+
+impl FancyCaptures {
+    /// Returns the captures of the first match starting from the given position.
+    fn find(regex: &FancyRegex, text: Arc<str>, pos: usize) -> Result<Option<Self>> {
+        let result = FancyCaptures::try_new(regex.clone(), text, |text| {
+            match regex.captures_from_pos(text, pos) {
+                Ok(Some(caps)) => Ok(caps),
+                Ok(None) => Err(None),
+                Err(err) => Err(Some(err)),
+            }
+        });
+        match result {
+            Ok(caps) => Ok(Some(caps)),
+            Err(None) => Ok(None),
+            Err(Some(err)) => Err(Error::external(err)),
+        }
+    }
+}
+
+impl CaptureGroups for FancyCaptures {
+    fn text(&self) -> &[u8] {
+        self.borrow_text().as_bytes()
+    }
+
+    fn get(&self, i: usize) -> Option<Range<usize>> {
+        self.borrow_caps().get(i).map(|m| m.range())
+    }
+
+    fn name(&self, name: &str) -> Option<Range<usize>> {
+        self.borrow_caps().name(name).map(|m| m.range())
+    }
+
+    fn len(&self) -> usize {
+        self.borrow_caps().len()
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.borrow_regex().capture_names().flatten()
+    }
+}
+
+impl UserData for FancyCaptures {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        register_captures(registry);
+    }
+}
@@ -0,0 +1,843 @@
+use std::ops::{Deref, Range};
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use mlua::{
+    AnyUserData, Error, FromLua, Function, IntoLuaMulti, Lua, MetaMethod, Result, String as LuaString, Table,
+    UserData, UserDataMethods, UserDataRefMut, Value, Variadic,
+};
+use ouroboros::self_referencing;
+
+use self::cache::RegexCache;
+
+pub use self::cache::{CacheMode, Config};
+#[cfg(feature = "regex-fancy")]
+pub use self::fancy::FancyRegex;
+
+/// A compiled regular expression.
+#[derive(Clone, Debug)]
+pub struct Regex(pub(crate) regex::bytes::Regex);
+
+impl Deref for Regex {
+    type Target = regex::bytes::Regex;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Applies the options to a `RegexBuilder` or `RegexSetBuilder`.
+macro_rules! apply_options {
+    ($builder:expr, $opts:expr) => {
+        $builder
+            .case_insensitive($opts.case_insensitive)
+            .multi_line($opts.multi_line)
+            .dot_matches_new_line($opts.dot_matches_new_line)
+            .unicode($opts.unicode)
+            .ignore_whitespace($opts.ignore_whitespace)
+            .crlf($opts.crlf);
+        if let Some(limit) = $opts.size_limit {
+            $builder.size_limit(limit);
+        }
+        if let Some(limit) = $opts.dfa_size_limit {
+            $builder.dfa_size_limit(limit);
+        }
+    };
+}
+
+/// The engine used to compile a regex.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// The `regex` crate engine, which guarantees linear time matching.
+    #[default]
+    Default,
+    /// The backtracking `fancy-regex` engine, which supports lookaround and backreferences.
+    #[cfg(feature = "regex-fancy")]
+    Fancy,
+}
+
+impl FromLua for Engine {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        match &*String::from_lua(value, lua)? {
+            "default" => Ok(Engine::Default),
+            #[cfg(feature = "regex-fancy")]
+            "fancy" => Ok(Engine::Fancy),
+            other => Err(Error::runtime(format!("unknown regex engine '{other}'"))),
+        }
+    }
+}
+
+/// Options used to compile a regex.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RegexOptions {
+    /// Match letters regardless of their case (`i` flag).
+    pub case_insensitive: bool,
+    /// Make `^` and `$` match at the beginning and end of lines (`m` flag).
+    pub multi_line: bool,
+    /// Allow `.` to match `\n` (`s` flag).
+    pub dot_matches_new_line: bool,
+    /// Enable Unicode support (`u` flag, enabled by default).
+    pub unicode: bool,
+    /// Ignore whitespace and allow `#` comments in the pattern (`x` flag).
+    pub ignore_whitespace: bool,
+    /// Treat `\r\n` as a line terminator in multi-line mode (`R` flag).
+    pub crlf: bool,
+    /// Approximate size limit (in bytes) of the compiled regex.
+    pub size_limit: Option<usize>,
+    /// Approximate size limit (in bytes) of the cache used by the lazy DFA.
+    pub dfa_size_limit: Option<usize>,
+}
+
+impl Default for RegexOptions {
+    fn default() -> Self {
+        RegexOptions {
+            case_insensitive: false,
+            multi_line: false,
+            dot_matches_new_line: false,
+            unicode: true,
+            ignore_whitespace: false,
+            crlf: false,
+            size_limit: None,
+            dfa_size_limit: None,
+        }
+    }
+}
+
+impl RegexOptions {
+    /// Reads the options from the `regex.new` options table.
+    fn from_table(opts: Option<&Table>) -> Result<Self> {
+        let default = RegexOptions::default();
+        Ok(RegexOptions {
+            case_insensitive: opt_param!(opts, "case_insensitive")?.unwrap_or(default.case_insensitive),
+            multi_line: opt_param!(opts, "multi_line")?.unwrap_or(default.multi_line),
+            dot_matches_new_line: opt_param!(opts, "dot_matches_new_line")?
+                .unwrap_or(default.dot_matches_new_line),
+            unicode: opt_param!(opts, "unicode")?.unwrap_or(default.unicode),
+            ignore_whitespace: opt_param!(opts, "ignore_whitespace")?.unwrap_or(default.ignore_whitespace),
+            crlf: opt_param!(opts, "crlf")?.unwrap_or(default.crlf),
+            size_limit: opt_param!(opts, "size_limit")?,
+            dfa_size_limit: opt_param!(opts, "dfa_size_limit")?,
+        })
+    }
+
+    pub(crate) fn build(&self, re: &str) -> StdResult<regex::bytes::Regex, regex::Error> {
+        let mut builder = regex::bytes::RegexBuilder::new(re);
+        apply_options!(builder, self);
+        builder.build()
+    }
+
+    pub(crate) fn build_set(&self, patterns: &[String]) -> StdResult<regex::bytes::RegexSet, regex::Error> {
+        let mut builder = regex::bytes::RegexSetBuilder::new(patterns);
+        apply_options!(builder, self);
+        builder.build()
+    }
+}
+
+/// State of the `regex` module in a Lua state.
+struct ModuleState {
+    cache: Arc<RegexCache>,
+    size_limit: Option<usize>,
+}
+
+impl ModuleState {
+    /// Returns the regex cache of the Lua state, or the global cache if the module is not
+    /// registered.
+    fn cache(lua: &Lua) -> Arc<RegexCache> {
+        match lua.app_data_ref::<ModuleState>() {
+            Some(state) => state.cache.clone(),
+            None => RegexCache::global(),
+        }
+    }
+
+    /// Returns the options with the size limit bounded by the module configuration.
+    fn options(lua: &Lua, opts: &RegexOptions) -> RegexOptions {
+        let mut opts = opts.clone();
+        if let Some(max) = lua
+            .app_data_ref::<ModuleState>()
+            .and_then(|state| state.size_limit)
+        {
+            opts.size_limit = Some(opts.size_limit.map_or(max, |limit| limit.min(max)));
+        }
+        opts
+    }
+}
+
+impl Regex {
+    /// Creates a new cached regex or retrieves it from the cache if it already exists.
+    pub fn new(lua: &Lua, re: &str) -> StdResult<Self, regex::Error> {
+        Self::with_options(lua, re, &RegexOptions::default())
+    }
+
+    /// Creates a new cached regex with the given options or retrieves it from the cache if it
+    /// already exists.
+    pub fn with_options(lua: &Lua, re: &str, opts: &RegexOptions) -> StdResult<Self, regex::Error> {
+        let opts = ModuleState::options(lua, opts);
+        ModuleState::cache(lua).get_or_compile(re, &opts)
+    }
+
+    /// Returns an iterator function over matches positions.
+    fn lua_find_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__regex_find_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(
+            |lua, mut it: UserDataRefMut<MatchesIter>| match it.next_range()? {
+                Some(range) => (range.start + 1, range.end).into_lua_multi(lua),
+                None => ().into_lua_multi(lua),
+            },
+        )?;
+        lua.set_named_registry_value("__regex_find_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Returns an iterator function over captures.
+    fn lua_captures_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__regex_captures_iterator") {
+            return Ok(f);
+        }
+
+        let f =
+            lua.create_function(
+                |lua, mut it: UserDataRefMut<MatchesIter>| match it.next_captures()? {
+                    Some(caps) => lua.create_userdata(caps)?.into_lua_multi(lua),
+                    None => ().into_lua_multi(lua),
+                },
+            )?;
+        lua.set_named_registry_value("__regex_captures_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Replaces at most `limit` matches in the text, returning the result and the number of
+    /// replaced matches.
+    ///
+    /// When the replacement function or table returns `nil` or `false`, the match is kept
+    /// unchanged, as in `string.gsub`.
+    fn lua_replacen(
+        &self,
+        lua: &Lua,
+        text: &[u8],
+        limit: usize,
+        rep: Replacement,
+    ) -> Result<(LuaString, usize)> {
+        if limit == 0 {
+            return Ok((lua.create_string(text)?, 0));
+        }
+        if let Replacement::Template(rep) = &rep {
+            let (rep, mut count) = (rep.as_bytes(), 0);
+            let output = self.replacen(text, limit, |caps: &regex::bytes::Captures| {
+                count += 1;
+                let mut dst = Vec::new();
+                caps.expand(&rep, &mut dst);
+                dst
+            });
+            return Ok((lua.create_string(output)?, count));
+        }
+
+        let mut it = MatchesIter::<Regex>::new(self, text);
+        let mut output = Vec::with_capacity(text.len());
+        let (mut last_end, mut count) = (0, 0);
+        while count < limit {
+            let (range, value) = match &rep {
+                Replacement::Function(f) => {
+                    let Some(caps) = it.next_captures()? else { break };
+                    let range = caps.borrow_caps().get_match().range();
+                    (range, f.call::<Value>(caps)?)
+                }
+                Replacement::Table(table) => {
+                    let Some(range) = it.next_range()? else { break };
+                    let value = table.get::<Value>(lua.create_string(&text[range.clone()])?)?;
+                    (range, value)
+                }
+                Replacement::Template(_) => unreachable!(),
+            };
+            output.extend_from_slice(&text[last_end..range.start]);
+            push_replacement(lua, &mut output, value, &text[range.clone()])?;
+            last_end = range.end;
+            count += 1;
+        }
+        output.extend_from_slice(&text[last_end..]);
+        Ok((lua.create_string(output)?, count))
+    }
+}
+
+impl UserData for Regex {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("is_match", |_, this, text: LuaString| {
+            Ok(this.0.is_match(&text.as_bytes()))
+        });
+
+        // Returns the start and end positions of the first match starting from `init`.
+        registry.add_method("find", |_, this, (text, init): (LuaString, Option<i64>)| {
+            let text = text.as_bytes();
+            let Some(init) = find_start(text.len(), init) else {
+                return Ok(Variadic::new());
+            };
+            match this.find_at(&text, init) {
+                Some(m) => Ok(Variadic::from_iter([m.start() + 1, m.end()])),
+                None => Ok(Variadic::new()),
+            }
+        });
+
+        // Returns an iterator over the start and end positions of all non-overlapping matches.
+        registry.add_method("find_iter", |lua, this, text: LuaString| {
+            let next = Self::lua_find_iterator(lua)?;
+            let iter_ud = AnyUserData::wrap(MatchesIter::<Regex>::new(this, &*text.as_bytes()));
+            (next, iter_ud).into_lua_multi(lua)
+        });
+
+        // Returns an iterator over the captures of all non-overlapping matches.
+        registry.add_method("captures_iter", |lua, this, text: LuaString| {
+            let next = Self::lua_captures_iterator(lua)?;
+            let iter_ud = AnyUserData::wrap(MatchesIter::<Regex>::new(this, &*text.as_bytes()));
+            (next, iter_ud).into_lua_multi(lua)
+        });
+
+        registry.add_method("match", |lua, this, text: LuaString| {
+            let text = (*text.as_bytes()).into();
+            let caps = Captures::try_new(this.clone(), text, |text| this.0.captures(text).ok_or(()));
+            match caps {
+                Ok(caps) => Ok(Value::UserData(lua.create_userdata(caps)?)),
+                Err(_) => Ok(Value::Nil),
+            }
+        });
+
+        // Returns the names of the named capture groups.
+        registry.add_method("capture_names", |lua, this, ()| {
+            lua.create_sequence_from(this.capture_names().flatten())
+        });
+
+        // Returns the total number of capture groups (including the whole match).
+        registry.add_method("captures_len", |_, this, ()| Ok(this.captures_len()));
+
+        // Returns low level information about raw offsets of each submatch.
+        registry.add_method("captures_read", |lua, this, text: LuaString| {
+            let mut locs = this.capture_locations();
+            match this.captures_read(&mut locs, &text.as_bytes()) {
+                Some(_) => Ok(Value::UserData(lua.create_userdata(CaptureLocations(locs))?)),
+                None => Ok(Value::Nil),
+            }
+        });
+
+        registry.add_method("split", |lua, this, text: LuaString| {
+            lua.create_sequence_from(this.split(&text.as_bytes()).map(LuaString::wrap))
+        });
+
+        registry.add_method("splitn", |lua, this, (text, limit): (LuaString, usize)| {
+            lua.create_sequence_from(this.splitn(&text.as_bytes(), limit).map(LuaString::wrap))
+        });
+
+        registry.add_method("replace", |lua, this, (text, rep): (LuaString, Replacement)| {
+            Ok(this.lua_replacen(lua, &text.as_bytes(), 1, rep)?.0)
+        });
+
+        // Replaces all matches, returning the result and the number of replaced matches.
+        registry.add_method(
+            "replace_all",
+            |lua, this, (text, rep): (LuaString, Replacement)| {
+                this.lua_replacen(lua, &text.as_bytes(), usize::MAX, rep)
+            },
+        );
+
+        // Replaces at most `limit` matches (none if `limit` is zero, as in `string.gsub`), returning
+        // the result and the number of replaced matches.
+        registry.add_method(
+            "replacen",
+            |lua, this, (text, limit, rep): (LuaString, usize, Replacement)| {
+                this.lua_replacen(lua, &text.as_bytes(), limit, rep)
+            },
+        );
+    }
+}
+
+/// State of the `find_iter` and `captures_iter` iterators.
+struct MatchesIter<R = Regex, S: ?Sized = [u8]> {
+    regex: R,
+    text: Arc<S>,
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<R: Clone, S: AsRef<[u8]> + ?Sized> MatchesIter<R, S> {
+    fn new(regex: &R, text: impl Into<Arc<S>>) -> Self {
+        MatchesIter {
+            regex: regex.clone(),
+            text: text.into(),
+            pos: 0,
+            last_end: None,
+        }
+    }
+
+    /// Finds the next match using the `find` function that searches from the given position.
+    ///
+    /// Empty matches immediately following the previous match are skipped, as in the `regex` crate
+    /// iterators.
+    fn next_with<T>(
+        &mut self,
+        find: impl Fn(&R, &Arc<S>, usize) -> Result<Option<(T, Range<usize>)>>,
+    ) -> Result<Option<T>> {
+        while self.pos <= (*self.text).as_ref().len() {
+            let Some((item, range)) = find(&self.regex, &self.text, self.pos)? else {
+                break;
+            };
+            if range.is_empty() && Some(range.end) == self.last_end {
+                self.pos = range.end + 1;
+                continue;
+            }
+            self.pos = range.end;
+            self.last_end = Some(range.end);
+            return Ok(Some(item));
+        }
+        Ok(None)
+    }
+}
+
+impl MatchesIter {
+    /// Returns the range of the next match.
+    fn next_range(&mut self) -> Result<Option<Range<usize>>> {
+        self.next_with(|regex, text, pos| Ok(regex.find_at(text, pos).map(|m| (m.range(), m.range()))))
+    }
+
+    /// Returns the captures of the next match.
+    fn next_captures(&mut self) -> Result<Option<Captures>> {
+        self.next_with(|regex, text, pos| {
+            let caps = Captures::try_new(regex.clone(), text.clone(), |text| {
+                regex.captures_at(text, pos).ok_or(())
+            });
+            Ok(caps.ok().map(|caps| {
+                let range = caps.borrow_caps().get_match().range();
+                (caps, range)
+            }))
+        })
+    }
+}
+
+#[self_referencing]
+struct Captures {
+    regex: Regex,
+    text: Arc<[u8]>,
+
+    #[borrows(text)]
+    #[covariant]
+    caps: regex::bytes::Captures<'this>,
+}
+
+impl CaptureGroups for Captures {
+    fn text(&self) -> &[u8] {
+        self.borrow_text()
+    }
+
+    fn get(&self, i: usize) -> Option<Range<usize>> {
+        self.borrow_caps().get(i).map(|m| m.range())
+    }
+
+    fn name(&self, name: &str) -> Option<Range<usize>> {
+        self.borrow_caps().name(name).map(|m| m.range())
+    }
+
+    fn len(&self) -> usize {
+        self.borrow_caps().len()
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.borrow_regex().capture_names().flatten()
+    }
+}
+
+impl UserData for Captures {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        register_captures(registry);
+    }
+}
+
+/// Capture groups of a match, shared by the regex engines.
+trait CaptureGroups: Sized + 'static {
+    /// Returns the searched text.
+    fn text(&self) -> &[u8];
+
+    /// Returns the range of a capture group by index.
+    fn get(&self, i: usize) -> Option<Range<usize>>;
+
+    /// Returns the range of a capture group by name.
+    fn name(&self, name: &str) -> Option<Range<usize>>;
+
+    /// Returns the total number of capture groups (including the whole match).
+    fn len(&self) -> usize;
+
+    /// Returns the names of the named capture groups.
+    fn names(&self) -> impl Iterator<Item = &str>;
+
+    /// Returns the range of a capture group by index or name.
+    fn group(&self, key: &Value) -> Option<Range<usize>> {
+        match key {
+            Value::String(s) => self.name(&s.to_string_lossy()),
+            Value::Integer(i) if *i >= 0 => self.get(*i as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the matched groups as pairs of numeric (starting from 0) or named keys and values.
+    ///
+    /// Numbered groups come first, followed by the named ones.
+    fn pairs(&self, lua: &Lua) -> Result<Vec<(Value, Value)>> {
+        let text = self.text();
+        let mut pairs = Vec::with_capacity(self.len());
+        for i in 0..self.len() {
+            if let Some(range) = self.get(i) {
+                pairs.push((
+                    Value::Integer(i as _),
+                    Value::String(lua.create_string(&text[range])?),
+                ));
+            }
+        }
+        for name in self.names() {
+            if let Some(range) = self.name(name) {
+                let key = Value::String(lua.create_string(name)?);
+                pairs.push((key, Value::String(lua.create_string(&text[range])?)));
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Converts the captures to a table with numeric (starting from 0) and named entries.
+    fn to_table(&self, lua: &Lua) -> Result<Table> {
+        let table = lua.create_table_with_capacity(self.len(), self.names().count())?;
+        for (key, value) in self.pairs(lua)? {
+            table.raw_set(key, value)?;
+        }
+        Ok(table)
+    }
+}
+
+/// State of the iterator over the matched groups of captures.
+struct CapturePairs(std::vec::IntoIter<(Value, Value)>);
+
+impl CapturePairs {
+    /// Returns an iterator function over the matched groups.
+    fn lua_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__regex_capture_pairs_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<CapturePairs>| match it.0.next() {
+            Some((key, value)) => (key, value).into_lua_multi(lua),
+            None => ().into_lua_multi(lua),
+        })?;
+        lua.set_named_registry_value("__regex_capture_pairs_iterator", &f)?;
+        Ok(f)
+    }
+}
+
+/// Registers the methods of a captures userdata.
+fn register_captures<T: CaptureGroups>(registry: &mut mlua::UserDataRegistry<T>) {
+    registry.add_meta_method(MetaMethod::Index, |lua, this, key: Value| {
+        (this.group(&key))
+            .map(|range| lua.create_string(&this.text()[range]))
+            .transpose()
+    });
+
+    // Returns the total number of capture groups (including the whole match).
+    registry.add_meta_method(MetaMethod::Len, |_, this, ()| Ok(this.len()));
+
+    // Returns the start position of a capture group (by index or name).
+    registry.add_method("start", |_, this, key: Value| {
+        Ok(this.group(&key).map(|range| range.start + 1))
+    });
+
+    // Returns the end position (inclusive) of a capture group (by index or name).
+    registry.add_method("finish", |_, this, key: Value| {
+        Ok(this.group(&key).map(|range| range.end))
+    });
+
+    // Returns the start and end positions of a capture group (by index or name).
+    registry.add_method("span", |_, this, key: Value| match this.group(&key) {
+        Some(range) => Ok(Variadic::from_iter([range.start + 1, range.end])),
+        None => Ok(Variadic::new()),
+    });
+
+    // Returns the names of the named capture groups.
+    registry.add_method("names", |lua, this, ()| lua.create_sequence_from(this.names()));
+
+    registry.add_method("to_table", |lua, this, ()| this.to_table(lua));
+
+    registry.add_meta_method(crate::METAMETHOD_ITER, |lua, this, ()| {
+        let next = CapturePairs::lua_iterator(lua)?;
+        let iter_ud = AnyUserData::wrap(CapturePairs(this.pairs(lua)?.into_iter()));
+        Ok((next, iter_ud))
+    });
+}
+
+/// A replacement for the matches of a regex.
+enum Replacement {
+    /// A template string where `$name` or `$1` are replaced with capture groups.
+    Template(LuaString),
+    /// A function called with the captures of each match.
+    Function(Function),
+    /// A table indexed by the whole match.
+    Table(Table),
+}
+
+impl FromLua for Replacement {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        match value {
+            Value::Function(f) => Ok(Replacement::Function(f)),
+            Value::Table(t) => Ok(Replacement::Table(t)),
+            value => match lua.coerce_string(value.clone())? {
+                Some(s) => Ok(Replacement::Template(s)),
+                None => Err(Error::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "Replacement".to_string(),
+                    message: Some("expected string, function or table".to_string()),
+                }),
+            },
+        }
+    }
+}
+
+/// Converts the 1-based `init` position of `find` into a byte offset.
+///
+/// Negative `init` counts from the end of the text, like in `string.find`. Returns `None` if the
+/// position is past the end of the text.
+fn find_start(len: usize, init: Option<i64>) -> Option<usize> {
+    let init = match init.unwrap_or(1) {
+        i if i < 0 => (len as i64 + i).max(0) as usize,
+        i => (i.max(1) - 1) as usize,
+    };
+    (init <= len).then_some(init)
+}
+
+/// Appends the replacement `value` of a match to the output.
+///
+/// `nil` and `false` keep the original match, as in `string.gsub`.
+fn push_replacement(lua: &Lua, output: &mut Vec<u8>, value: Value, matched: &[u8]) -> Result<()> {
+    match value {
+        Value::Nil | Value::Boolean(false) => output.extend_from_slice(matched),
+        value => match lua.coerce_string(value.clone())? {
+            Some(s) => output.extend_from_slice(&s.as_bytes()),
+            None => {
+                let msg = format!("invalid replacement value (a {})", value.type_name());
+                return Err(Error::runtime(msg));
+            }
+        },
+    }
+    Ok(())
+}
+
+struct CaptureLocations(regex::bytes::CaptureLocations);
+
+impl UserData for CaptureLocations {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        // Returns the total number of capture groups.
+        registry.add_method("len", |_, this, ()| Ok(this.0.len()));
+
+        // Returns the start and end positions of the Nth capture group.
+        registry.add_method("get", |_, this, i: usize| match this.0.get(i) {
+            // We add 1 to the start position because Lua is 1-indexed.
+            // End position is non-inclusive, so we don't need to add 1.
+            Some((start, end)) => Ok(Variadic::from_iter([start + 1, end])),
+            None => Ok(Variadic::new()),
+        });
+    }
+}
+
+/// A set of regular expressions matched in a single pass.
+#[derive(Clone, Debug)]
+pub struct RegexSet {
+    set: regex::bytes::RegexSet,
+    opts: RegexOptions,
+}
+
+impl Deref for RegexSet {
+    type Target = regex::bytes::RegexSet;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.set
+    }
+}
+
+impl RegexSet {
+    /// Creates a new cached regex set with the given options or retrieves it from the cache if it
+    /// already exists.
+    pub fn with_options(
+        lua: &Lua,
+        patterns: &[String],
+        opts: &RegexOptions,
+    ) -> StdResult<Self, regex::Error> {
+        let opts = ModuleState::options(lua, opts);
+        ModuleState::cache(lua).get_or_compile_set(patterns, &opts)
+    }
+
+    /// Returns the regex of the pattern at the given (0-based) index.
+    fn regex(&self, lua: &Lua, i: usize) -> Option<StdResult<Regex, regex::Error>> {
+        let pattern = self.patterns().get(i)?;
+        Some(Regex::with_options(lua, pattern, &self.opts))
+    }
+}
+
+impl UserData for RegexSet {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        // Compiles a set of patterns, accepting the same options as `regex.new`.
+        registry.add_function("new", |lua, (patterns, opts): (Vec<String>, Option<Table>)| {
+            if opt_param!(opts, "engine")?.is_some_and(|engine: Engine| engine != Engine::Default) {
+                return Ok(Err("regex sets support only the default engine".to_string()));
+            }
+            let opts = RegexOptions::from_table(opts.as_ref())?;
+            Ok(Ok(lua_try!(RegexSet::with_options(lua, &patterns, &opts))))
+        });
+
+        registry.add_method("is_match", |_, this, text: LuaString| {
+            Ok(this.is_match(&text.as_bytes()))
+        });
+
+        registry.add_method("len", |_, this, ()| Ok(this.len()));
+
+        registry.add_method("matches", |_, this, text: LuaString| {
+            Ok(this
+                .matches(&text.as_bytes())
+                .iter()
+                .map(|i| i + 1)
+                .collect::<Vec<_>>())
+        });
+
+        registry.add_method("patterns", |_, this, ()| Ok(this.patterns().to_vec()));
+
+        // Returns the regex of the Nth pattern.
+        registry.add_method("regex", |lua, this, i: usize| {
+            match i.checked_sub(1).and_then(|i| this.regex(lua, i)) {
+                Some(re) => Ok(Ok(Some(lua_try!(re)))),
+                None => Ok(Ok(None)),
+            }
+        });
+
+        // Returns the index of the first pattern that matches and its captures.
+        registry.add_method("first_match", |lua, this, text: LuaString| {
+            let text = text.as_bytes();
+            let Some(i) = this.matches(&text).iter().next() else {
+                return ().into_lua_multi(lua);
+            };
+            let re = (this.regex(lua, i).expect("pattern index is valid")).map_err(Error::external)?;
+            let caps = Captures::try_new(re.clone(), (*text).into(), |text| re.captures(text).ok_or(()));
+            match caps {
+                Ok(caps) => (i + 1, caps).into_lua_multi(lua),
+                Err(_) => ().into_lua_multi(lua),
+            }
+        });
+    }
+}
+
+/// Compiles a regular expression.
+///
+/// Once compiled, it can be used repeatedly to search, split or replace substrings in a text.
+///
+/// The optional `opts` table can contain:
+/// - `case_insensitive` (boolean): Match letters regardless of their case. Default is false.
+/// - `multi_line` (boolean): Make `^` and `$` match at the beginning and end of lines.
+///   Default is false.
+/// - `dot_matches_new_line` (boolean): Allow `.` to match `\n`. Default is false.
+/// - `unicode` (boolean): Enable Unicode support. Default is true.
+/// - `ignore_whitespace` (boolean): Ignore whitespace and allow `#` comments in the pattern.
+///   Default is false.
+/// - `crlf` (boolean): Treat `\r\n` as a line terminator in multi-line mode. Default is false.
+/// - `size_limit` (integer): Approximate size limit (in bytes) of the compiled regex.
+/// - `dfa_size_limit` (integer): Approximate size limit (in bytes) of the lazy DFA cache.
+/// - `engine` (string): The regex engine, "default" or "fancy". The "fancy" engine (requires the
+///   `regex-fancy` feature) supports lookaround and backreferences, but matches only UTF-8 text
+///   and does not support the `crlf` option. Default is "default".
+pub fn new(lua: &Lua, (re, opts): (LuaString, Option<Table>)) -> Result<StdResult<AnyUserData, String>> {
+    let re = re.to_str()?;
+    let engine: Engine = opt_param!(opts, "engine")?.unwrap_or_default();
+    let opts = RegexOptions::from_table(opts.as_ref())?;
+    match engine {
+        Engine::Default => {
+            let re = lua_try!(Regex::with_options(lua, &re, &opts));
+            Ok(Ok(lua.create_userdata(re)?))
+        }
+        #[cfg(feature = "regex-fancy")]
+        Engine::Fancy => {
+            if opts.crlf {
+                return Ok(Err(
+                    "the fancy engine does not support the `crlf` option".to_string()
+                ));
+            }
+            let re = lua_try!(FancyRegex::with_options(lua, &re, &opts));
+            Ok(Ok(lua.create_userdata(re)?))
+        }
+    }
+}
+
+/// Escapes a string so that it can be used as a literal in a regular expression.
+pub fn escape(_: &Lua, text: LuaString) -> Result<String> {
+    Ok(regex::escape(&text.to_str()?))
+}
+
+/// Returns true if there is a match for the regex anywhere in the given text.
+pub fn is_match(lua: &Lua, (re, text): (LuaString, LuaString)) -> Result<StdResult<bool, String>> {
+    let re = re.to_str()?;
+    let re = lua_try!(Regex::new(lua, &re));
+    Ok(Ok(re.is_match(&text.as_bytes())))
+}
+
+/// Returns all matches of the regex in the given text or nil if there is no match.
+pub fn r#match(lua: &Lua, (re, text): (LuaString, LuaString)) -> Result<StdResult<Value, String>> {
+    let re = re.to_str()?;
+    let re = lua_try!(Regex::new(lua, &re));
+    match re.captures(&text.as_bytes()) {
+        Some(caps) => {
+            let mut it = caps.iter().map(|om| om.map(|m| LuaString::wrap(m.as_bytes())));
+            let first = it.next().unwrap();
+            let table = lua.create_sequence_from(it)?;
+            table.raw_set(0, first)?;
+            Ok(Ok(Value::Table(table)))
+        }
+        None => Ok(Ok(Value::Nil)),
+    }
+}
+
+/// Returns the statistics of the regex cache used by the Lua state.
+///
+/// The global cache statistics include regexes compiled by all Lua states.
+pub fn cache_stats(lua: &Lua, _: ()) -> Result<Table> {
+    let stats = ModuleState::cache(lua).stats();
+    let t = lua.create_table()?;
+    t.set("hits", stats.hits)?;
+    t.set("misses", stats.misses)?;
+    t.set("evictions", stats.evictions)?;
+    t.set("size", stats.size)?;
+    t.set("capacity", stats.capacity)?;
+    Ok(t)
+}
+
+/// A loader for the `regex` module.
+fn loader(lua: &Lua) -> Result<Table> {
+    let t = lua.create_table()?;
+    t.set("new", lua.create_function(new)?)?;
+    t.set("escape", lua.create_function(escape)?)?;
+    t.set("is_match", lua.create_function(is_match)?)?;
+    t.set("match", lua.create_function(r#match)?)?;
+    t.set("RegexSet", lua.create_proxy::<RegexSet>()?)?;
+    t.set("cache_stats", lua.create_function(cache_stats)?)?;
+    Ok(t)
+}
+
+/// Registers the `regex` module in the given Lua state.
+pub fn register(lua: &Lua, name: Option<&str>) -> Result<Table> {
+    register_with_config(lua, name, Config::default())
+}
+
+/// Registers the `regex` module in the given Lua state using the given configuration.
+pub fn register_with_config(lua: &Lua, name: Option<&str>, config: Config) -> Result<Table> {
+    let name = name.unwrap_or("@regex");
+    lua.set_app_data(ModuleState {
+        cache: RegexCache::from_config(&config),
+        size_limit: config.size_limit,
+    });
+    let value = loader(lua)?;
+    lua.register_module(name, &value)?;
+    Ok(value)
+}
+
+mod cache;
+#[cfg(feature = "regex-fancy")]
+mod fancy;
@@ -0,0 +1,733 @@
+use std::cell::OnceCell;
+use std::ops::Range;
+use std::result::Result as StdResult;
+
+use mlua::{
+    Error, Lua, MetaMethod, Result, String as LuaString, UserData, UserDataMethods, UserDataRegistry, Value,
+};
+use saphyr_parser::{Event, Parser, ScalarStyle, ScanError};
+
+use super::{DecodeContext, parse_all, parse_index, pointer_tokens, yaml_to_lua};
+use crate::error::DecodeError;
+use crate::hooks;
+
+/// A YAML document that keeps comments and formatting when edited.
+///
+/// The source text is stored as is, and every edit replaces only the text of the changed value.
+pub(crate) struct YamlDocument {
+    text: String,
+    root: Option<Node>,
+    // Parsed value of the document, reset on every edit
+    value: OnceCell<Option<serde_yaml::Value>>,
+}
+
+/// A node of the document tree, with its location (in bytes) in the source text.
+struct Node {
+    span: Range<usize>,
+    kind: NodeKind,
+    anchor: Option<Range<usize>>,
+    tag: Option<Range<usize>>,
+    /// Location of the `-` indicator if the node is an item of a block sequence.
+    dash: Option<usize>,
+}
+
+enum NodeKind {
+    Scalar(String, ScalarStyle),
+    Alias,
+    Sequence { items: Vec<Node>, flow: bool },
+    Mapping { entries: Vec<(Node, Node)>, flow: bool },
+}
+
+/// A text replacement in the document.
+type Edit = (Range<usize>, String);
+
+impl Node {
+    fn new(span: Range<usize>, kind: NodeKind) -> Self {
+        Node {
+            span,
+            kind,
+            anchor: None,
+            tag: None,
+            dash: None,
+        }
+    }
+
+    /// Returns the start of the node, including its anchor and tag.
+    fn start(&self) -> usize {
+        [self.anchor.as_ref(), self.tag.as_ref()]
+            .into_iter()
+            .flatten()
+            .fold(self.span.start, |start, range| start.min(range.start))
+    }
+
+    /// Returns the child node at the given JSON Pointer reference token.
+    fn child(&self, token: &str) -> Option<&Node> {
+        match &self.kind {
+            NodeKind::Sequence { items, .. } => items.get(parse_index(token)?),
+            NodeKind::Mapping { entries, .. } => {
+                let (_, value) = entries.iter().find(|(key, _)| key.is_key(token))?;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if this node is a scalar with the given value.
+    fn is_key(&self, token: &str) -> bool {
+        matches!(&self.kind, NodeKind::Scalar(value, _) if value == token)
+    }
+
+    fn is_block_collection(&self) -> bool {
+        matches!(
+            self.kind,
+            NodeKind::Sequence { flow: false, .. } | NodeKind::Mapping { flow: false, .. }
+        )
+    }
+
+    fn is_flow_collection(&self) -> bool {
+        matches!(
+            self.kind,
+            NodeKind::Sequence { flow: true, .. } | NodeKind::Mapping { flow: true, .. }
+        )
+    }
+}
+
+impl YamlDocument {
+    /// Parses the given text into a document.
+    ///
+    /// Only the first document of a multi-document stream can be edited.
+    pub(crate) fn parse(text: String) -> StdResult<Self, DecodeError> {
+        match parse_tree(&text) {
+            Ok(root) => Ok(YamlDocument {
+                text,
+                root,
+                value: OnceCell::new(),
+            }),
+            Err(err) => Err(DecodeError::from_yaml_scan(err, &text)),
+        }
+    }
+
+    /// Returns the node at the given JSON Pointer path.
+    fn find(&self, path: &str) -> Option<&Node> {
+        let tokens = pointer_tokens(path)?;
+        (tokens.iter()).try_fold(self.root.as_ref()?, |node, token| node.child(token))
+    }
+
+    /// Returns the value of the document, parsing it on first use.
+    fn value(&self) -> Result<Option<&serde_yaml::Value>> {
+        if self.value.get().is_none() {
+            let docs = parse_all(self.text.as_bytes()).map_err(Error::external)?;
+            let _ = self.value.set(docs.into_iter().next());
+        }
+        Ok(self.value.get().and_then(Option::as_ref))
+    }
+
+    /// Returns a copy of the value at the given JSON Pointer path.
+    fn get(&self, path: &str) -> Result<Option<serde_yaml::Value>> {
+        let (Some(tokens), Some(doc)) = (pointer_tokens(path), self.value()?) else {
+            return Ok(None);
+        };
+        let value = (tokens.iter()).try_fold(doc, |value, token| super::lookup(value, token));
+        Ok(value.cloned())
+    }
+
+    /// Sets the value at the given JSON Pointer path.
+    ///
+    /// Missing mapping keys are appended after the last entry, and the `-` token (or an index equal
+    /// to the length) appends to sequences.
+    fn set(&mut self, path: &str, value: &serde_yaml::Value) -> Result<()> {
+        let Some((parent_path, token)) = path.rsplit_once('/') else {
+            if !path.is_empty() {
+                return Err(Error::runtime(format!("invalid JSON pointer '{path}'")));
+            }
+            let edit = match &self.root {
+                Some(root) => {
+                    let (start, props) = self.kept_properties(root, value);
+                    let rendered = render_block(value, self.column(root.span.start))?;
+                    let sep = if is_block(value) { "\n" } else { " " };
+                    match props.is_empty() {
+                        true => (start..root.span.end, rendered),
+                        false => (start..root.span.end, format!("{props}{sep}{rendered}")),
+                    }
+                }
+                None => {
+                    let len = self.text.len();
+                    let sep = if self.text.is_empty() || self.text.ends_with('\n') {
+                        ""
+                    } else {
+                        "\n"
+                    };
+                    (len..len, format!("{sep}{}\n", render_block(value, 0)?))
+                }
+            };
+            return self.apply(edit);
+        };
+        let token = token.replace("~1", "/").replace("~0", "~");
+
+        let Some(parent) = self.find(parent_path) else {
+            return Err(Error::runtime(format!("path '{parent_path}' does not exist")));
+        };
+        let edit = match &parent.kind {
+            NodeKind::Mapping { entries, flow } => match entries.iter().find(|(key, _)| key.is_key(&token)) {
+                Some((key, old)) => self.replace_entry(key, old, *flow, value)?,
+                None => self.insert_entry(parent, entries, *flow, &token, value)?,
+            },
+            NodeKind::Sequence { items, flow } => match parse_index(&token) {
+                Some(i) if i < items.len() => self.replace_item(&items[i], *flow, value)?,
+                Some(i) if i == items.len() => self.append_item(parent, items, *flow, value)?,
+                _ if token == "-" => self.append_item(parent, items, *flow, value)?,
+                _ => {
+                    return Err(Error::runtime(format!("array index '{token}' is out of bounds")));
+                }
+            },
+            _ => {
+                let err = format!("cannot set '{token}' on a primitive value at '{parent_path}'");
+                return Err(Error::runtime(err));
+            }
+        };
+        self.apply(edit)
+    }
+
+    /// Removes the value at the given JSON Pointer path, returning it.
+    ///
+    /// Whole lines are removed from block collections, so comments on other lines are kept.
+    fn remove(&mut self, path: &str) -> Result<Option<serde_yaml::Value>> {
+        let Some((parent_path, token)) = path.rsplit_once('/') else {
+            return Err(Error::runtime("cannot remove the root value"));
+        };
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let Some(parent) = self.find(parent_path) else {
+            return Ok(None);
+        };
+
+        // Spans of all elements of the parent, including keys and sequence indicators
+        let (elements, index, flow) = match &parent.kind {
+            NodeKind::Mapping { entries, flow } => {
+                let Some(index) = entries.iter().position(|(key, _)| key.is_key(&token)) else {
+                    return Ok(None);
+                };
+                let elements = (entries.iter())
+                    .map(|(key, value)| key.start()..value.span.end.max(key.span.end))
+                    .collect::<Vec<_>>();
+                (elements, index, *flow)
+            }
+            NodeKind::Sequence { items, flow } => {
+                let Some(index) = parse_index(&token).filter(|&i| i < items.len()) else {
+                    return Ok(None);
+                };
+                let elements = (items.iter())
+                    .map(|item| match item.dash {
+                        Some(dash) => dash..item.span.end,
+                        None => item.start()..item.span.end,
+                    })
+                    .collect::<Vec<_>>();
+                (elements, index, *flow)
+            }
+            _ => return Ok(None),
+        };
+
+        let value = self.get(path)?;
+        if elements.len() == 1 && !flow {
+            // Block collections cannot be empty, so replace the parent with an empty flow collection
+            let empty = match parent.kind {
+                NodeKind::Mapping { .. } => serde_yaml::Value::Mapping(Default::default()),
+                _ => serde_yaml::Value::Sequence(Default::default()),
+            };
+            self.set(parent_path, &empty)?;
+            return Ok(value);
+        }
+
+        let span = elements[index].clone();
+        let range = if flow {
+            match (index.checked_sub(1), elements.get(index + 1)) {
+                (Some(prev), _) => elements[prev].end..span.end,
+                (None, Some(next)) => span.start..next.start,
+                (None, None) => span,
+            }
+        } else {
+            let line_start = self.line_start(span.start);
+            match (
+                self.text[line_start..span.start].trim().is_empty(),
+                elements.get(index + 1),
+            ) {
+                (true, _) => line_start..(self.line_end(span.end) + 1).min(self.text.len()),
+                // The element shares its line with the parent (eg. `- key: value`)
+                (false, Some(next)) => span.start..next.start,
+                (false, None) => span,
+            }
+        };
+        self.apply((range, String::new()))?;
+        Ok(value)
+    }
+
+    /// Replaces the value of an existing mapping entry.
+    fn replace_entry(&self, key: &Node, old: &Node, flow: bool, value: &serde_yaml::Value) -> Result<Edit> {
+        let (start, props) = self.kept_properties(old, value);
+        if flow {
+            let range = start..old.span.end;
+            let prefix = if range.is_empty() { " " } else { "" };
+            let rendered = with_properties(&props, render_flow(value)?);
+            return Ok((range, format!("{prefix}{rendered}")));
+        }
+        let props = match props.is_empty() {
+            true => props,
+            false => format!(" {props}"),
+        };
+
+        let column = self.column(key.span.start);
+        if is_block(value) {
+            // Keep the indentation of the old value if possible
+            let indent = match (&old.kind, value) {
+                (NodeKind::Mapping { flow: false, .. }, _) => self.column(old.span.start),
+                (NodeKind::Sequence { flow: false, .. }, serde_yaml::Value::Sequence(_)) => {
+                    self.column(old.span.start)
+                }
+                _ => column + 2,
+            };
+            let rendered = render_block(value, indent)?;
+            return Ok((
+                key.span.end..old.span.end,
+                format!(":{props}\n{}{rendered}", " ".repeat(indent)),
+            ));
+        }
+
+        let rendered = self.render_inline(old, value, column)?;
+        // Replace only the old value when it is on the same line as the key, without anchors or tags
+        let between = &self.text[key.span.end..old.span.start.max(key.span.end)];
+        let only_indicator =
+            (between.trim_start().strip_prefix(':')).is_some_and(|rest| rest.trim().is_empty());
+        if !old.span.is_empty() && !old.is_block_collection() && only_indicator && !between.contains('\n') {
+            return Ok((old.span.clone(), rendered));
+        }
+        Ok((
+            key.span.end..old.span.end.max(key.span.end),
+            format!(":{props} {rendered}"),
+        ))
+    }
+
+    /// Appends a new entry to a mapping.
+    fn insert_entry(
+        &self,
+        parent: &Node,
+        entries: &[(Node, Node)],
+        flow: bool,
+        token: &str,
+        value: &serde_yaml::Value,
+    ) -> Result<Edit> {
+        let key = render_key(token, flow)?;
+        if flow {
+            let rendered = format!("{key}: {}", render_flow(value)?);
+            return Ok(match entries.last() {
+                Some((_, last)) => (last.span.end..last.span.end, format!(", {rendered}")),
+                None => (parent.span.end - 1..parent.span.end - 1, rendered),
+            });
+        }
+
+        // Block mappings always have at least one entry
+        let (first_key, _) = &entries[0];
+        let (last_key, last) = &entries[entries.len() - 1];
+        let indent = self.column(first_key.span.start);
+        let pos = self.line_end(last.span.end.max(last_key.span.end));
+        let rendered = match is_block(value) {
+            true => format!(
+                ":\n{0}{1}",
+                " ".repeat(indent + 2),
+                render_block(value, indent + 2)?
+            ),
+            false => format!(": {}", render_nested(value, indent)?),
+        };
+        Ok((pos..pos, format!("\n{}{key}{rendered}", " ".repeat(indent))))
+    }
+
+    /// Replaces an existing sequence item.
+    fn replace_item(&self, item: &Node, flow: bool, value: &serde_yaml::Value) -> Result<Edit> {
+        let (start, props) = self.kept_properties(item, value);
+        if flow {
+            return Ok((start..item.span.end, with_properties(&props, render_flow(value)?)));
+        }
+        let column = self.column(item.dash.unwrap_or(item.span.start));
+        let rendered = self.render_inline(item, value, column)?;
+        // Properties of block collections go on the line of the `-` indicator
+        let rendered = match is_block(value) && !item.is_flow_collection() && !props.is_empty() {
+            true => format!("{props}\n{}{rendered}", " ".repeat(column + 2)),
+            false => with_properties(&props, rendered),
+        };
+        // Empty items end right after the `-` indicator
+        match (start..item.span.end).is_empty() {
+            true => Ok((start..item.span.end, format!(" {rendered}"))),
+            false => Ok((start..item.span.end, rendered)),
+        }
+    }
+
+    /// Appends a new item to a sequence.
+    fn append_item(
+        &self,
+        parent: &Node,
+        items: &[Node],
+        flow: bool,
+        value: &serde_yaml::Value,
+    ) -> Result<Edit> {
+        if flow {
+            let rendered = render_flow(value)?;
+            return Ok(match items.last() {
+                Some(last) => (last.span.end..last.span.end, format!(", {rendered}")),
+                None => (parent.span.end - 1..parent.span.end - 1, rendered),
+            });
+        }
+
+        // Block sequences start at the indicator of the first item
+        let indent = self.column(parent.span.start);
+        let pos = self.line_end(items[items.len() - 1].span.end);
+        let rendered = render_nested(value, indent)?;
+        Ok((pos..pos, format!("\n{}- {rendered}", " ".repeat(indent))))
+    }
+
+    /// Renders a value that replaces the given node on the same line, keeping the node style.
+    ///
+    /// The `indent` is the column of the key or `-` indicator before the node.
+    fn render_inline(&self, old: &Node, value: &serde_yaml::Value, indent: usize) -> Result<String> {
+        match (&old.kind, value) {
+            (NodeKind::Scalar(_, ScalarStyle::DoubleQuoted), serde_yaml::Value::String(s)) => {
+                serde_json::to_string(s).map_err(Error::external)
+            }
+            (NodeKind::Scalar(_, ScalarStyle::SingleQuoted), serde_yaml::Value::String(s))
+                if !s.contains('\n') =>
+            {
+                Ok(format!("'{}'", s.replace('\'', "''")))
+            }
+            (_, serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_))
+                if old.is_flow_collection() =>
+            {
+                render_flow(value)
+            }
+            _ => render_nested(value, indent),
+        }
+    }
+
+    /// Returns the start of the text to replace for the `old` node, and the properties of the node to
+    /// keep in front of the new value.
+    ///
+    /// The anchor is always kept, so aliases keep referring to the value. The tag is kept unless the
+    /// new value has its own tag.
+    fn kept_properties(&self, old: &Node, value: &serde_yaml::Value) -> (usize, String) {
+        let tag = old
+            .tag
+            .clone()
+            .filter(|_| !matches!(value, serde_yaml::Value::Tagged(_)));
+        let props = [old.anchor.clone(), tag]
+            .into_iter()
+            .flatten()
+            .map(|range| &self.text[range])
+            .collect::<Vec<_>>();
+        (old.start(), props.join(" "))
+    }
+
+    /// Applies the edit to the source text and parses it again.
+    ///
+    /// The document is left unchanged if the edited text is not valid YAML.
+    fn apply(&mut self, (range, replacement): Edit) -> Result<()> {
+        let mut text = self.text.clone();
+        text.replace_range(range, &replacement);
+        match parse_tree(&text) {
+            Ok(root) => {
+                self.text = text;
+                self.root = root;
+                self.value = OnceCell::new();
+                Ok(())
+            }
+            Err(err) => Err(Error::runtime(format!("edit produced invalid YAML: {err}"))),
+        }
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.text[..pos].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.text[pos..].find('\n').map_or(self.text.len(), |i| pos + i)
+    }
+
+    /// Returns the column (in characters) of the given position.
+    fn column(&self, pos: usize) -> usize {
+        self.text[self.line_start(pos)..pos].chars().count()
+    }
+}
+
+/// Checks if the value is rendered over multiple lines in block style.
+fn is_block(value: &serde_yaml::Value) -> bool {
+    match value {
+        serde_yaml::Value::Sequence(seq) => !seq.is_empty(),
+        serde_yaml::Value::Mapping(map) => !map.is_empty(),
+        _ => false,
+    }
+}
+
+/// Renders a value in block style, indenting all lines but the first by `indent` columns.
+fn render_block(value: &serde_yaml::Value, indent: usize) -> Result<String> {
+    let text = serde_yaml::to_string(value).map_err(Error::external)?;
+    let mut output = String::with_capacity(text.len());
+    for (i, line) in text.trim_end_matches('\n').split('\n').enumerate() {
+        if i > 0 {
+            output.push('\n');
+            if !line.is_empty() {
+                output.push_str(&" ".repeat(indent));
+            }
+        }
+        output.push_str(line);
+    }
+    Ok(output)
+}
+
+/// Renders a value placed after a key or `-` indicator at column `indent`.
+fn render_nested(value: &serde_yaml::Value, indent: usize) -> Result<String> {
+    // Block scalars are already indented by the serializer
+    match value {
+        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) | serde_yaml::Value::Tagged(_) => {
+            render_block(value, indent + 2)
+        }
+        _ => render_block(value, indent),
+    }
+}
+
+/// Renders a value in flow style.
+fn render_flow(value: &serde_yaml::Value) -> Result<String> {
+    match value {
+        serde_yaml::Value::Sequence(seq) => {
+            let items = seq.iter().map(render_flow).collect::<Result<Vec<_>>>()?;
+            Ok(format!("[{}]", items.join(", ")))
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let entries = (map.iter())
+                .map(|(key, value)| Ok(format!("{}: {}", render_flow(key)?, render_flow(value)?)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(format!("{{{}}}", entries.join(", ")))
+        }
+        serde_yaml::Value::Tagged(tagged) => Ok(format!("{} {}", tagged.tag, render_flow(&tagged.value)?)),
+        // Plain strings may contain flow indicators, so they are always quoted
+        serde_yaml::Value::String(s) => serde_json::to_string(s).map_err(Error::external),
+        _ => render_block(value, 0),
+    }
+}
+
+/// Puts the kept properties of a replaced node in front of the rendered value.
+fn with_properties(props: &str, rendered: String) -> String {
+    match props.is_empty() {
+        true => rendered,
+        false => format!("{props} {rendered}"),
+    }
+}
+
+/// Renders a mapping key, quoting it if needed.
+fn render_key(key: &str, flow: bool) -> Result<String> {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || "_-./".contains(c));
+    match flow && !plain {
+        true => serde_json::to_string(key).map_err(Error::external),
+        false => render_block(&serde_yaml::Value::String(key.to_string()), 0),
+    }
+}
+
+/// Parses the first document of the text into a tree of nodes.
+fn parse_tree(text: &str) -> StdResult<Option<Node>, ScanError> {
+    // Parser locations are in characters
+    let offsets = (text.char_indices().map(|(i, _)| i))
+        .chain([text.len()])
+        .collect::<Vec<_>>();
+    let mut events = Vec::new();
+    for event in Parser::new_from_str(text) {
+        let (event, span) = event?;
+        events.push((event, offsets[span.start.index()]..offsets[span.end.index()]));
+    }
+
+    let mut builder = TreeBuilder {
+        text,
+        events: events.into_iter(),
+    };
+    while let Some((event, span)) = builder.events.next() {
+        if let Event::DocumentStart(_) = event {
+            return Ok(builder.next_node(span.end));
+        }
+    }
+    Ok(None)
+}
+
+struct TreeBuilder<'a, I> {
+    text: &'a str,
+    events: I,
+}
+
+impl<'a, I> TreeBuilder<'a, I>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    /// Builds the next node, whose anchor and tag are written after the `from` position.
+    fn next_node(&mut self, from: usize) -> Option<Node> {
+        let (event, span) = self.events.next()?;
+        self.node(event, span, from)
+    }
+
+    /// Builds a node from the start event, returning `None` on the end of a collection.
+    fn node(&mut self, event: Event<'a>, span: Range<usize>, from: usize) -> Option<Node> {
+        let text = self.text;
+        let (mut node, anchor, tag) = match event {
+            Event::Scalar(value, style, anchor, tag) => {
+                // Block scalar spans start at the content, after the indicator
+                let start = match style {
+                    ScalarStyle::Literal | ScalarStyle::Folded => {
+                        text[..span.start].rfind(['|', '>']).unwrap_or(span.start)
+                    }
+                    _ => span.start,
+                };
+                let end = span.start + text[span.clone()].trim_end().len();
+                let kind = NodeKind::Scalar(value.into_owned(), style);
+                (Node::new(start..end, kind), anchor, tag.is_some())
+            }
+            Event::Alias(_) => (Node::new(span, NodeKind::Alias), 0, false),
+            Event::SequenceStart(anchor, tag) => {
+                let flow = text[span.clone()].starts_with('[');
+                let mut items = Vec::new();
+                // Block sequences start at the `-` indicator of the first item
+                let mut prev_end = span.start;
+                let end = loop {
+                    match self.events.next() {
+                        Some((Event::SequenceEnd, end)) => break end.end,
+                        Some((event, span)) => {
+                            let dash = (!flow).then(|| find_dash(text, prev_end)).flatten();
+                            let Some(mut item) = self.node(event, span, dash.map_or(prev_end, |i| i + 1))
+                            else {
+                                continue;
+                            };
+                            item.dash = dash;
+                            prev_end = item.span.end;
+                            items.push(item);
+                        }
+                        None => break span.end,
+                    }
+                };
+                let span = match (flow, items.first(), items.last()) {
+                    (false, Some(first), Some(last)) => first.dash.unwrap_or(first.span.start)..last.span.end,
+                    _ => span.start..end,
+                };
+                let kind = NodeKind::Sequence { items, flow };
+                (Node::new(span, kind), anchor, tag.is_some())
+            }
+            Event::MappingStart(anchor, tag) => {
+                let flow = text[span.clone()].starts_with('{');
+                let mut entries = Vec::new();
+                let mut prev_end = span.start;
+                let end = loop {
+                    let key = match self.events.next() {
+                        Some((Event::MappingEnd, end)) => break end.end,
+                        Some((event, span)) => self.node(event, span, prev_end)?,
+                        None => break span.end,
+                    };
+                    let Some(mut value) = self.next_node(key.span.end) else {
+                        break key.span.end;
+                    };
+                    // Empty values are located at the end of the key, before the `:` indicator
+                    if value.span.is_empty() && value.span.start <= key.span.end {
+                        let rest = &text[key.span.end..];
+                        let trimmed = rest.trim_start();
+                        if trimmed.starts_with(':') {
+                            let pos = key.span.end + (rest.len() - trimmed.len()) + 1;
+                            value.span = pos..pos;
+                        }
+                    }
+                    prev_end = value.span.end.max(key.span.end);
+                    entries.push((key, value));
+                };
+                let span = match (flow, entries.first(), entries.last()) {
+                    (false, Some((first, _)), Some((last_key, last))) => {
+                        first.start()..last.span.end.max(last_key.span.end)
+                    }
+                    _ => span.start..end,
+                };
+                let kind = NodeKind::Mapping { entries, flow };
+                (Node::new(span, kind), anchor, tag.is_some())
+            }
+            Event::SequenceEnd | Event::MappingEnd => return None,
+            _ => return self.next_node(from),
+        };
+        if anchor > 0 || tag {
+            let (anchor_range, tag_range) = find_properties(text, from..node.span.start);
+            node.anchor = anchor_range.filter(|_| anchor > 0);
+            node.tag = tag_range.filter(|_| tag);
+        }
+        Some(node)
+    }
+}
+
+/// Returns the position of the `-` indicator after `pos`, skipping whitespace and comments.
+fn find_dash(text: &str, pos: usize) -> Option<usize> {
+    let mut rest = &text[pos..];
+    loop {
+        let trimmed = rest.trim_start();
+        match trimmed.strip_prefix('#') {
+            Some(comment) => rest = comment.find('\n').map_or("", |i| &comment[i..]),
+            None if trimmed.starts_with('-') => return Some(text.len() - trimmed.len()),
+            None => return None,
+        }
+    }
+}
+
+/// Finds the anchor (`&name`) and tag (`!tag`) properties of a node within the given range.
+///
+/// The range must cover the text between the previous indicator and the node content.
+fn find_properties(text: &str, range: Range<usize>) -> (Option<Range<usize>>, Option<Range<usize>>) {
+    let (mut anchor, mut tag) = (None, None);
+    let mut pos = range.start;
+    while pos < range.end {
+        let rest = &text[pos..range.end];
+        if rest.starts_with('#') {
+            pos += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with(['&', '!']) {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || ",[]{}".contains(c))
+                .unwrap_or(rest.len());
+            match rest.starts_with('&') {
+                true => anchor = Some(pos..pos + len),
+                false => tag = Some(pos..pos + len),
+            }
+            pos += len;
+            continue;
+        }
+        pos += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    (anchor, tag)
+}
+
+/// Converts a Lua value into a YAML value, applying `__serialize` metamethods.
+fn lua_to_yaml(lua: &Lua, value: Value) -> Result<serde_yaml::Value> {
+    let encoder = hooks::Encoder::new(lua, super::ENCODE_METAMETHODS, None).yaml_tags(true);
+    let value = encoder.wrap(value)?;
+    encoder
+        .finish(super::to_yaml_value(&value))?
+        .map_err(Error::external)
+}
+
+impl UserData for YamlDocument {
+    fn register(registry: &mut UserDataRegistry<Self>) {
+        registry.add_method("get", |lua, this, path: LuaString| {
+            match this.get(&path.to_str()?)? {
+                Some(value) => yaml_to_lua(lua, &value, &DecodeContext::default())?.map_err(Error::external),
+                None => Ok(Value::Nil),
+            }
+        });
+
+        registry.add_method_mut("set", |lua, this, (path, value): (LuaString, Value)| {
+            this.set(&path.to_str()?, &lua_to_yaml(lua, value)?)
+        });
+
+        registry.add_method_mut("remove", |lua, this, path: LuaString| {
+            match this.remove(&path.to_str()?)? {
+                Some(value) => yaml_to_lua(lua, &value, &DecodeContext::default())?.map_err(Error::external),
+                None => Ok(Value::Nil),
+            }
+        });
+
+        registry.add_method("to_string", |_, this, ()| Ok(this.text.clone()));
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.text.clone()));
+    }
+}
@@ -0,0 +1,350 @@
+use mlua::{Error, FromLua, Lua, Result, Table, Value};
+
+/// The quoting style of YAML strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum QuoteStyle {
+    /// Quote strings only when required, preferring single quotes.
+    #[default]
+    Auto,
+    /// Always use single quotes (double quotes for strings that cannot be single-quoted).
+    Single,
+    /// Always use double quotes.
+    Double,
+}
+
+impl FromLua for QuoteStyle {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        match &*String::from_lua(value, lua)? {
+            "auto" => Ok(QuoteStyle::Auto),
+            "single" => Ok(QuoteStyle::Single),
+            "double" => Ok(QuoteStyle::Double),
+            other => Err(Error::runtime(format!("unknown quote style '{other}'"))),
+        }
+    }
+}
+
+/// A YAML emitter with configurable indentation, flow style, quoting and line width.
+///
+/// With the default settings it produces the same output as `serde_yaml`.
+pub(crate) struct Emitter {
+    /// Number of spaces used for each nesting level.
+    pub(crate) indent: usize,
+    /// Indent sequences nested in mappings instead of aligning them with the parent key.
+    pub(crate) indent_sequences: bool,
+    /// Nesting level from which collections are written in flow style (`[1, 2]`, `{a: 1}`).
+    pub(crate) flow_level: Option<usize>,
+    /// Quoting style of string values. Keys are quoted only when needed.
+    pub(crate) quote_style: QuoteStyle,
+    /// Preferred maximum line width. Long strings are folded at spaces to fit.
+    pub(crate) line_width: Option<usize>,
+    /// Start the document with a `---` marker.
+    pub(crate) explicit_start: bool,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            indent: 2,
+            indent_sequences: false,
+            flow_level: None,
+            quote_style: QuoteStyle::Auto,
+            line_width: None,
+            explicit_start: false,
+        }
+    }
+}
+
+impl Emitter {
+    /// Builds the emitter from the `encode` options table.
+    pub(crate) fn new(opts: Option<&Table>) -> Result<Self> {
+        let mut emitter = Emitter::default();
+        if let Some(indent) = opt_param!(opts, "indent")? {
+            if indent == 0 {
+                return Err(Error::runtime("`indent` must be greater than zero"));
+            }
+            emitter.indent = indent;
+        }
+        emitter.indent_sequences = opt_param!(opts, "indent_sequences")?.unwrap_or(false);
+        emitter.flow_level = opt_param!(opts, "flow_level")?;
+        emitter.quote_style = opt_param!(opts, "quote_style")?.unwrap_or_default();
+        emitter.line_width = opt_param!(opts, "line_width")?;
+        emitter.explicit_start = opt_param!(opts, "explicit_start")?.unwrap_or(false);
+        Ok(emitter)
+    }
+
+    /// Returns true if the emitter produces the same output as the default `serde_yaml` one.
+    pub(crate) fn is_default(&self) -> bool {
+        self.indent == 2
+            && !self.indent_sequences
+            && self.flow_level.is_none()
+            && self.quote_style == QuoteStyle::Auto
+            && self.line_width.is_none()
+            && !self.explicit_start
+    }
+
+    /// Emits a YAML document, ending with a newline.
+    pub(crate) fn emit(&self, value: &serde_yaml::Value) -> Result<String> {
+        let mut output = String::new();
+        if self.explicit_start {
+            output.push_str("---\n");
+        }
+        match self.block_collection(value, 0) {
+            Some((tag, collection)) => {
+                if let Some(tag) = tag {
+                    output.push_str(&format!("{tag}\n"));
+                }
+                self.write_block(&mut output, collection, 0, 0)?;
+            }
+            None => {
+                let inline = self.inline(value, 0, false)?;
+                output.push_str(&self.fold(value, inline, 0, self.indent));
+            }
+        }
+        output.push('\n');
+        Ok(output)
+    }
+
+    /// Returns the (optionally tagged) collection if the value is written in block style at the
+    /// given nesting level.
+    fn block_collection<'a>(
+        &self,
+        value: &'a serde_yaml::Value,
+        depth: usize,
+    ) -> Option<(Option<String>, &'a serde_yaml::Value)> {
+        if self.flow_level.is_some_and(|level| depth >= level) {
+            return None;
+        }
+        match value {
+            serde_yaml::Value::Sequence(seq) if !seq.is_empty() => Some((None, value)),
+            serde_yaml::Value::Mapping(map) if !map.is_empty() => Some((None, value)),
+            serde_yaml::Value::Tagged(tagged) => {
+                let (_, inner) = self.block_collection(&tagged.value, depth)?;
+                Some((Some(tagged.tag.to_string()), inner))
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes a non-empty block collection whose first line starts at the current position.
+    ///
+    /// Collection elements are placed at the `column` indentation.
+    fn write_block(
+        &self,
+        out: &mut String,
+        value: &serde_yaml::Value,
+        column: usize,
+        depth: usize,
+    ) -> Result<()> {
+        match value {
+            serde_yaml::Value::Sequence(seq) => {
+                for (i, item) in seq.iter().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(column));
+                    }
+                    out.push('-');
+                    self.write_nested(out, item, column, column + 2, depth + 1)?;
+                }
+            }
+            serde_yaml::Value::Mapping(map) => {
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(column));
+                    }
+                    // Complex and multi-line keys are written as explicit `? key` entries
+                    let multiline = matches!(key, serde_yaml::Value::String(s) if s.contains('\n'));
+                    if self.block_collection(key, depth + 1).is_some() || multiline {
+                        out.push('?');
+                        self.write_nested(out, key, column, column + 2, depth + 1)?;
+                        out.push('\n');
+                        out.push_str(&" ".repeat(column));
+                    } else {
+                        out.push_str(&self.key(key, false)?);
+                    }
+                    out.push(':');
+                    let seq_column = match self.indent_sequences {
+                        true => column + self.indent,
+                        false => column,
+                    };
+                    let nested_column = match self.block_collection(value, depth + 1) {
+                        Some((_, serde_yaml::Value::Sequence(_))) => seq_column,
+                        _ => column + self.indent,
+                    };
+                    self.write_nested(out, value, column, nested_column, depth + 1)?;
+                }
+            }
+            _ => unreachable!("not a block collection"),
+        }
+        Ok(())
+    }
+
+    /// Writes a value after a `-`/`?` indicator or a mapping key placed at the `parent` column.
+    ///
+    /// Block collections start on a new line at `column`, except untagged collections after the
+    /// `-` and `?` indicators which start on the same line.
+    fn write_nested(
+        &self,
+        out: &mut String,
+        value: &serde_yaml::Value,
+        parent: usize,
+        column: usize,
+        depth: usize,
+    ) -> Result<()> {
+        let compact = out.ends_with(['-', '?']);
+        match self.block_collection(value, depth) {
+            Some((tag, collection)) => {
+                if let Some(tag) = &tag {
+                    out.push(' ');
+                    out.push_str(tag);
+                }
+                match tag.is_none() && compact {
+                    true => out.push(' '),
+                    false => {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(column));
+                    }
+                }
+                self.write_block(out, collection, column, depth)
+            }
+            None => {
+                let inline = self.inline(value, parent, false)?;
+                out.push(' ');
+                let fold_column = match compact {
+                    true => parent + 2,
+                    false => parent + self.indent,
+                };
+                let start = current_column(out);
+                out.push_str(&self.fold(value, inline, start, fold_column));
+                Ok(())
+            }
+        }
+    }
+
+    /// Renders a scalar or a flow collection on a single line (block scalars excepted).
+    ///
+    /// Content lines of block scalars are indented relative to `parent_indent`.
+    fn inline(&self, value: &serde_yaml::Value, parent_indent: usize, flow: bool) -> Result<String> {
+        match value {
+            serde_yaml::Value::Sequence(seq) => {
+                let items = (seq.iter())
+                    .map(|item| self.inline(item, parent_indent, true))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("[{}]", items.join(", ")))
+            }
+            serde_yaml::Value::Mapping(map) => {
+                let entries = (map.iter())
+                    .map(|(key, value)| {
+                        let key = self.key(key, true)?;
+                        Ok(format!("{key}: {}", self.inline(value, parent_indent, true)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("{{{}}}", entries.join(", ")))
+            }
+            serde_yaml::Value::Tagged(tagged) => Ok(format!(
+                "{} {}",
+                tagged.tag,
+                self.inline(&tagged.value, parent_indent, flow)?
+            )),
+            serde_yaml::Value::String(s) => self.string(s, parent_indent, flow, self.quote_style),
+            _ => Ok(to_yaml_string(value)?),
+        }
+    }
+
+    /// Renders a mapping key. String keys are quoted only when needed.
+    fn key(&self, key: &serde_yaml::Value, flow: bool) -> Result<String> {
+        match key {
+            serde_yaml::Value::String(s) => self.string(s, 0, flow, QuoteStyle::Auto),
+            _ => self.inline(key, 0, flow),
+        }
+    }
+
+    fn string(&self, s: &str, parent_indent: usize, flow: bool, style: QuoteStyle) -> Result<String> {
+        match style {
+            QuoteStyle::Double => double_quote(s),
+            QuoteStyle::Single if s.contains('\n') || s.chars().any(char::is_control) => double_quote(s),
+            QuoteStyle::Single => Ok(format!("'{}'", s.replace('\'', "''"))),
+            QuoteStyle::Auto if flow && s.contains('\n') => double_quote(s),
+            QuoteStyle::Auto => {
+                let text = to_yaml_string(&serde_yaml::Value::String(s.to_string()))?;
+                // Plain scalars cannot contain flow indicators in flow context
+                if flow && !text.starts_with(['\'', '"']) && text.contains([',', '[', ']', '{', '}']) {
+                    return Ok(format!("'{}'", s.replace('\'', "''")));
+                }
+                let (header, content) = match text.split_once('\n') {
+                    Some((header, content)) if header.starts_with(['|', '>']) => (header, content),
+                    _ => return Ok(text),
+                };
+                // Block scalars are indented by two spaces, use the configured indentation instead
+                // (indentation indicators are single digits, so they are kept for larger indents)
+                let has_indicator = header.contains(|c: char| c.is_ascii_digit());
+                let indent = if has_indicator && self.indent > 9 {
+                    2
+                } else {
+                    self.indent
+                };
+                let header = header.replace(|c: char| c.is_ascii_digit(), &indent.to_string());
+                let prefix = format!("\n{}", " ".repeat(parent_indent + indent));
+                Ok(format!(
+                    "{header}{}",
+                    format!("\n{content}").replace("\n  ", &prefix)
+                ))
+            }
+        }
+    }
+
+    /// Folds a string rendered on a single line at spaces to fit in the line width.
+    ///
+    /// The string starts at the `start` column, and folded lines are indented by `column`.
+    fn fold(&self, value: &serde_yaml::Value, text: String, start: usize, column: usize) -> String {
+        let Some(width) = self.line_width else {
+            return text;
+        };
+        let is_string = match value {
+            serde_yaml::Value::Tagged(tagged) => tagged.value.is_string(),
+            value => value.is_string(),
+        };
+        if !is_string || text.contains('\n') || start + text.chars().count() <= width {
+            return text;
+        }
+
+        let mut output = String::with_capacity(text.len());
+        let mut line_len = start;
+        let mut words = text.split(' ').peekable();
+        while let Some(word) = words.next() {
+            output.push_str(word);
+            line_len += word.chars().count();
+            let Some(next) = words.peek() else {
+                break;
+            };
+            // Only single spaces between words can be folded without changing the value
+            let foldable = !word.is_empty() && !next.is_empty() && line_len > column;
+            if foldable && line_len + 1 + next.chars().count() > width {
+                output.push('\n');
+                output.push_str(&" ".repeat(column));
+                line_len = column;
+            } else {
+                output.push(' ');
+                line_len += 1;
+            }
+        }
+        output
+    }
+}
+
+/// Returns the column of the end of the output.
+fn current_column(out: &str) -> usize {
+    let line_start = out.rfind('\n').map_or(0, |i| i + 1);
+    out[line_start..].chars().count()
+}
+
+/// Renders a scalar value using the `serde_yaml` serializer.
+fn to_yaml_string(value: &serde_yaml::Value) -> Result<String> {
+    let text = serde_yaml::to_string(value).map_err(Error::external)?;
+    Ok(text.trim_end_matches('\n').to_string())
+}
+
+/// Renders a double-quoted string. JSON strings are valid double-quoted YAML scalars.
+fn double_quote(s: &str) -> Result<String> {
+    serde_json::to_string(s).map_err(Error::external)
+}
@@ -0,0 +1,885 @@
+use std::convert;
+use std::mem;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use mlua::{
+    AnyUserData, Error, Function, Integer as LuaInteger, IntoLuaMulti, Lua, LuaSerdeExt, MetaMethod,
+    MultiValue, Result, SerializeOptions, String as LuaString, Table, UserData, UserDataFields,
+    UserDataMethods, UserDataRefMut, Value,
+};
+use ouroboros::self_referencing;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json_path::{JsonPath, NormalizedPath, PathElement};
+use serde_yaml::value::{Tag, TaggedValue};
+
+use crate::bytes::StringOrBytes;
+use crate::error::DecodeError;
+use crate::hooks;
+use document::YamlDocument;
+use format::Emitter;
+
+/// Represents a native YAML object in Lua.
+#[derive(Clone)]
+pub(crate) struct YamlObject {
+    root: Arc<serde_yaml::Value>,
+    current: *const serde_yaml::Value,
+}
+
+impl Serialize for YamlObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        let value = SerializeValue {
+            value: self.current(),
+            tags: false,
+        };
+        value.serialize(serializer)
+    }
+}
+
+/// Serializes a YAML value, either without tags or with tags carried as [`TAGGED_VARIANT`].
+struct SerializeValue<'a> {
+    value: &'a serde_yaml::Value,
+    tags: bool,
+}
+
+impl SerializeValue<'_> {
+    fn with<'a>(&self, value: &'a serde_yaml::Value) -> SerializeValue<'a> {
+        SerializeValue {
+            value,
+            tags: self.tags,
+        }
+    }
+}
+
+impl Serialize for SerializeValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        match self.value {
+            serde_yaml::Value::Sequence(seq) => serializer.collect_seq(seq.iter().map(|v| self.with(v))),
+            serde_yaml::Value::Mapping(map) => {
+                serializer.collect_map(map.iter().map(|(k, v)| (self.with(k), self.with(v))))
+            }
+            serde_yaml::Value::Tagged(tagged) if self.tags => {
+                let value = (tagged.tag.to_string(), self.with(&tagged.value));
+                serializer.serialize_newtype_variant("Tagged", 0, TAGGED_VARIANT, &value)
+            }
+            serde_yaml::Value::Tagged(tagged) => self.with(&tagged.value).serialize(serializer),
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+impl YamlObject {
+    /// Creates a new `YamlObject` from the given YAML value.
+    ///
+    /// SAFETY:
+    /// The caller must ensure that `current` is a value inside `root`.
+    unsafe fn new(root: &Arc<serde_yaml::Value>, current: &serde_yaml::Value) -> Self {
+        let root = root.clone();
+        YamlObject { root, current }
+    }
+
+    /// Returns a reference to the current YAML value.
+    #[inline(always)]
+    fn current(&self) -> &serde_yaml::Value {
+        unsafe { &*self.current }
+    }
+
+    /// Returns a new `YamlObject` which points to the value at the given key.
+    ///
+    /// This operation is cheap and does not clone the underlying data.
+    ///
+    /// Sequences are indexed by (1-based) integers, and mappings by any key that can be converted
+    /// to a YAML value (including other `YamlObject`s returned as complex keys).
+    fn get(&self, key: Value) -> Option<Self> {
+        let value = match (self.current(), key) {
+            (serde_yaml::Value::Sequence(seq), Value::Integer(index)) if index > 0 => {
+                seq.get(index as usize - 1)
+            }
+            (serde_yaml::Value::Sequence(_), _) => None,
+            (serde_yaml::Value::Mapping(map), key) => map.get(lua_key_to_yaml(key)?),
+            (current, Value::String(key)) => key.to_str().ok().and_then(|s| current.get(&*s)),
+            _ => None,
+        }?;
+        unsafe { Some(Self::new(&self.root, value)) }
+    }
+
+    /// Returns a new `YamlObject` by following the given JSON Pointer path from the current value.
+    ///
+    /// Mapping keys are matched by their string form and tags are skipped.
+    fn pointer(&self, path: &str) -> Option<Self> {
+        let tokens = pointer_tokens(path)?;
+        let value = (tokens.iter()).try_fold(self.current(), |value, token| lookup(value, token))?;
+        unsafe { Some(YamlObject::new(&self.root, value)) }
+    }
+
+    /// Returns the type name of the current value.
+    fn type_name(&self) -> &'static str {
+        match self.current() {
+            serde_yaml::Value::Null => "null",
+            serde_yaml::Value::Bool(_) => "boolean",
+            serde_yaml::Value::Number(_) => "number",
+            serde_yaml::Value::String(_) => "string",
+            serde_yaml::Value::Sequence(_) => "array",
+            serde_yaml::Value::Mapping(_) => "object",
+            serde_yaml::Value::Tagged(_) => "tagged",
+        }
+    }
+
+    /// Returns the number of elements in a sequence or the number of keys in a mapping.
+    fn len(&self) -> usize {
+        match self.current() {
+            serde_yaml::Value::Sequence(seq) => seq.len(),
+            serde_yaml::Value::Mapping(map) => map.len(),
+            _ => 0,
+        }
+    }
+
+    /// Returns the keys of a mapping, or the (1-based) indices of a sequence.
+    fn keys(&self, lua: &Lua) -> Result<Table> {
+        match self.current() {
+            serde_yaml::Value::Sequence(seq) => lua.create_sequence_from(1..=seq.len()),
+            serde_yaml::Value::Mapping(map) => {
+                let keys = (map.keys())
+                    .map(|key| unsafe { YamlObject::new(&self.root, key) }.into_lua(lua))
+                    .collect::<Result<Vec<_>>>()?;
+                lua.create_sequence_from(keys)
+            }
+            _ => lua.create_table(),
+        }
+    }
+
+    /// Returns all values matching the given JSONPath query, relative to the current value.
+    ///
+    /// The query is evaluated against a JSON view of the current value and the matches are resolved
+    /// back in the original document, so they do not clone the underlying data.
+    ///
+    /// Fails if two keys of a mapping have the same string form (e.g. `1` and `"1"`).
+    fn query(&self, path: &JsonPath) -> StdResult<Vec<Self>, String> {
+        let json = yaml_to_json(self.current())?;
+        Ok((path.query_located(&json).iter())
+            .filter_map(|node| resolve_location(self.current(), node.location()))
+            .map(|value| unsafe { YamlObject::new(&self.root, value) })
+            .collect())
+    }
+
+    /// Converts this `YamlObject` into a Lua `Value`.
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        match self.current() {
+            serde_yaml::Value::Null => Ok(Value::NULL),
+            serde_yaml::Value::Bool(b) => Ok(Value::Boolean(*b)),
+            serde_yaml::Value::Number(n) => {
+                if let Some(n) = n.as_i64() {
+                    Ok(Value::Integer(n as _))
+                } else if let Some(n) = n.as_f64() {
+                    Ok(Value::Number(n))
+                } else {
+                    Err(Error::ToLuaConversionError {
+                        from: "number".to_string(),
+                        to: "integer or float",
+                        message: Some("number is too big to fit in a Lua integer".to_owned()),
+                    })
+                }
+            }
+            serde_yaml::Value::String(s) => Ok(Value::String(lua.create_string(s)?)),
+            value @ serde_yaml::Value::Sequence(_) | value @ serde_yaml::Value::Mapping(_) => {
+                let obj_ud = lua.create_ser_userdata(unsafe { YamlObject::new(&self.root, value) })?;
+                Ok(Value::UserData(obj_ud))
+            }
+            serde_yaml::Value::Tagged(tagged) => {
+                let value = unsafe { YamlObject::new(&self.root, &tagged.value) }.into_lua(lua)?;
+                Tagged::new(&tagged.tag.to_string(), value).into_lua(lua)
+            }
+        }
+    }
+
+    fn lua_iterator(&self, lua: &Lua) -> Result<MultiValue> {
+        match self.current() {
+            serde_yaml::Value::Sequence(_) => {
+                let next = Self::lua_array_iterator(lua)?;
+                let iter_ud = AnyUserData::wrap(LuaYamlArrayIter {
+                    value: self.clone(),
+                    next: 1, // index starts at 1
+                });
+                (next, iter_ud).into_lua_multi(lua)
+            }
+            serde_yaml::Value::Mapping(_) => {
+                let next = Self::lua_map_iterator(lua)?;
+                let iter_builder = LuaYamlMapIterBuilder {
+                    value: self.clone(),
+                    iter_builder: |value| value.current().as_mapping().unwrap().iter(),
+                };
+                let iter_ud = AnyUserData::wrap(iter_builder.build());
+                (next, iter_ud).into_lua_multi(lua)
+            }
+            _ => ().into_lua_multi(lua),
+        }
+    }
+
+    /// Returns an iterator function for arrays.
+    fn lua_array_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__yaml_array_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<LuaYamlArrayIter>| {
+            it.next += 1;
+            match it.value.get(Value::Integer(it.next - 1)) {
+                Some(next_value) => (it.next - 1, next_value.into_lua(lua)?).into_lua_multi(lua),
+                None => ().into_lua_multi(lua),
+            }
+        })?;
+        lua.set_named_registry_value("__yaml_array_iterator", &f)?;
+        Ok(f)
+    }
+
+    /// Returns an iterator function for objects.
+    fn lua_map_iterator(lua: &Lua) -> Result<Function> {
+        if let Ok(Some(f)) = lua.named_registry_value("__yaml_map_iterator") {
+            return Ok(f);
+        }
+
+        let f = lua.create_function(|lua, mut it: UserDataRefMut<LuaYamlMapIter>| {
+            let root = it.borrow_value().root.clone();
+            it.with_iter_mut(move |iter| match iter.next() {
+                Some((key, value)) => {
+                    // Complex keys are returned as native objects
+                    let key = unsafe { YamlObject::new(&root, key) }.into_lua(lua)?;
+                    let value = unsafe { YamlObject::new(&root, value) }.into_lua(lua)?;
+                    (key, value).into_lua_multi(lua)
+                }
+                None => ().into_lua_multi(lua),
+            })
+        })?;
+        lua.set_named_registry_value("__yaml_map_iterator", &f)?;
+        Ok(f)
+    }
+}
+
+/// Converts a Lua value used to index a mapping into a YAML key.
+fn lua_key_to_yaml(key: Value) -> Option<serde_yaml::Value> {
+    match key {
+        Value::Boolean(b) => Some(serde_yaml::Value::Bool(b)),
+        Value::Integer(i) => Some(serde_yaml::Value::Number(i.into())),
+        Value::Number(n) => Some(serde_yaml::Value::Number(n.into())),
+        Value::String(s) => Some(serde_yaml::Value::String(s.to_str().ok()?.to_owned())),
+        Value::UserData(ud) => Some(ud.borrow::<YamlObject>().ok()?.current().clone()),
+        _ => None,
+    }
+}
+
+/// Converts a YAML value into a JSON value for evaluating JSONPath queries.
+///
+/// Tags are ignored, scalar keys are converted to strings and other keys are skipped. Keys that
+/// have the same string form cannot be told apart, so they are reported as an error.
+fn yaml_to_json(value: &serde_yaml::Value) -> StdResult<serde_json::Value, String> {
+    let json = match value {
+        serde_yaml::Value::Null => serde_json::Value::Null,
+        serde_yaml::Value::Bool(b) => serde_json::Value::Bool(*b),
+        serde_yaml::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => serde_json::Value::from(i),
+            (_, Some(u)) => serde_json::Value::from(u),
+            _ => serde_json::Value::from(n.as_f64()),
+        },
+        serde_yaml::Value::String(s) => serde_json::Value::String(s.clone()),
+        serde_yaml::Value::Sequence(seq) => {
+            serde_json::Value::Array(seq.iter().map(yaml_to_json).collect::<StdResult<_, _>>()?)
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let mut object = serde_json::Map::with_capacity(map.len());
+            for (key, value) in map {
+                let Some(key) = yaml_key_to_string(key) else {
+                    continue;
+                };
+                if object.contains_key(&key) {
+                    return Err(format!("ambiguous mapping key '{key}'"));
+                }
+                object.insert(key, yaml_to_json(value)?);
+            }
+            serde_json::Value::Object(object)
+        }
+        serde_yaml::Value::Tagged(tagged) => yaml_to_json(&tagged.value)?,
+    };
+    Ok(json)
+}
+
+/// Converts a scalar YAML mapping key into a string.
+fn yaml_key_to_string(key: &serde_yaml::Value) -> Option<String> {
+    match key {
+        serde_yaml::Value::Null => Some("null".to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Tagged(tagged) => yaml_key_to_string(&tagged.value),
+        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) => None,
+    }
+}
+
+/// Splits a JSON Pointer path into unescaped reference tokens.
+fn pointer_tokens(path: &str) -> Option<Vec<String>> {
+    if path.is_empty() {
+        return Some(Vec::new());
+    }
+    let tokens = path.strip_prefix('/')?.split('/');
+    Some(
+        tokens
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Parses a JSON Pointer array index, rejecting signs and leading zeros.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.starts_with(['+', '-']) || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Returns the value at the given JSON Pointer reference token, skipping tags.
+///
+/// Mapping keys are matched by their string form.
+fn lookup<'a>(mut value: &'a serde_yaml::Value, token: &str) -> Option<&'a serde_yaml::Value> {
+    while let serde_yaml::Value::Tagged(tagged) = value {
+        value = &tagged.value;
+    }
+    match value {
+        serde_yaml::Value::Sequence(seq) => seq.get(parse_index(token)?),
+        serde_yaml::Value::Mapping(map) => {
+            let (_, value) =
+                (map.iter()).find(|(key, _)| yaml_key_to_string(key).as_deref() == Some(token))?;
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+/// Finds the value at the given location (produced by [`yaml_to_json`]) in a YAML value.
+fn resolve_location<'a>(
+    mut value: &'a serde_yaml::Value,
+    location: &NormalizedPath,
+) -> Option<&'a serde_yaml::Value> {
+    for element in location.iter() {
+        while let serde_yaml::Value::Tagged(tagged) = value {
+            value = &tagged.value;
+        }
+        value = match (value, element) {
+            (serde_yaml::Value::Sequence(seq), PathElement::Index(i)) => seq.get(*i)?,
+            (serde_yaml::Value::Mapping(map), PathElement::Name(name)) => {
+                let (_, value) =
+                    (map.iter()).find(|(key, _)| yaml_key_to_string(key).as_deref() == Some(*name))?;
+                value
+            }
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+impl From<serde_yaml::Value> for YamlObject {
+    fn from(value: serde_yaml::Value) -> Self {
+        let root = Arc::new(value);
+        unsafe { Self::new(&root, &root) }
+    }
+}
+
+impl UserData for YamlObject {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_method("dump", |lua, this, ()| {
+            yaml_to_lua(lua, this.current(), &DecodeContext::default())?.map_err(Error::external)
+        });
+
+        registry.add_method("pointer", |lua, this, path: LuaString| {
+            this.pointer(&path.to_str()?)
+                .map(|obj| obj.into_lua(lua))
+                .unwrap_or(Ok(Value::Nil))
+        });
+
+        registry.add_method("keys", |lua, this, ()| this.keys(lua));
+
+        // Returns the type of the current value or the value at the given key
+        registry.add_method("type", |_, this, key: Option<Value>| match key {
+            Some(key) => Ok(this.get(key).map(|obj| obj.type_name())),
+            None => Ok(Some(this.type_name())),
+        });
+
+        registry.add_method("is_null", |_, this, key: Option<Value>| match key {
+            Some(key) => Ok(this.get(key).is_some_and(|obj| obj.current().is_null())),
+            None => Ok(this.current().is_null()),
+        });
+
+        registry.add_method("query", |lua, this, path: LuaString| {
+            let path = lua_try!(JsonPath::parse(&path.to_str()?));
+            let matches = (lua_try!(this.query(&path)).into_iter())
+                .map(|obj| obj.into_lua(lua))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Ok(lua.create_sequence_from(matches)?))
+        });
+
+        registry.add_method("query_one", |lua, this, path: LuaString| {
+            let path = lua_try!(JsonPath::parse(&path.to_str()?));
+            match lua_try!(this.query(&path)).into_iter().next() {
+                Some(obj) => Ok(Ok(obj.into_lua(lua)?)),
+                None => Ok(Ok(Value::Nil)),
+            }
+        });
+
+        registry.add_method("iter", |lua, this, ()| this.lua_iterator(lua));
+
+        registry.add_meta_method(MetaMethod::Index, |lua, this, key: Value| {
+            this.get(key)
+                .map(|obj| obj.into_lua(lua))
+                .unwrap_or(Ok(Value::Nil))
+        });
+
+        registry.add_meta_method(MetaMethod::Len, |_, this, ()| Ok(this.len()));
+
+        registry.add_meta_function(MetaMethod::Eq, |_, (a, b): (AnyUserData, AnyUserData)| {
+            match (a.borrow::<Self>(), b.borrow::<Self>()) {
+                (Ok(a), Ok(b)) => Ok(a.current() == b.current()),
+                _ => Ok(false),
+            }
+        });
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            serde_yaml::to_string(this.current()).map_err(Error::external)
+        });
+
+        registry.add_meta_method(crate::METAMETHOD_ITER, |lua, this, ()| this.lua_iterator(lua));
+    }
+}
+
+struct LuaYamlArrayIter {
+    value: YamlObject,
+    next: LuaInteger,
+}
+
+#[self_referencing]
+struct LuaYamlMapIter {
+    value: YamlObject,
+
+    #[borrows(value)]
+    #[covariant]
+    iter: serde_yaml::mapping::Iter<'this>,
+}
+
+/// A YAML value with a tag, such as `!secret value`.
+#[derive(Clone)]
+pub(crate) struct Tagged {
+    tag: String,
+    value: Value,
+}
+
+impl Tagged {
+    /// Creates a new `Tagged` value, adding the `!` prefix to the tag if missing.
+    fn new(tag: &str, value: Value) -> Self {
+        let tag = match tag.starts_with('!') {
+            true => tag.to_string(),
+            false => format!("!{tag}"),
+        };
+        Tagged { tag, value }
+    }
+
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        Ok(Value::UserData(lua.create_ser_userdata(self)?))
+    }
+}
+
+// Serializers other than YAML get the plain value
+impl Serialize for Tagged {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        self.value.to_serializable().serialize(serializer)
+    }
+}
+
+/// Metamethods used to customize encoding of tables and userdata.
+const ENCODE_METAMETHODS: &[&str] = &["__serialize"];
+
+/// Variant name used to carry `Tagged` values through serialization.
+///
+/// mlua serializes userdata through a type-erased serializer, which cannot pass a tag of a
+/// `TaggedValue` to serde_yaml. Tagged values are serialized as this variant with a `(tag, value)`
+/// pair instead, and [`to_yaml_value`] turns them back into tagged nodes.
+const TAGGED_VARIANT: &str = "mlua_stdlib::yaml::Tagged";
+
+/// Serializes `Tagged` and native object userdata with their tags, as [`TAGGED_VARIANT`] values.
+///
+/// Returns the serializer back if the userdata is of another type.
+pub(crate) fn serialize_tagged<S: Serializer>(
+    ud: &AnyUserData,
+    encoder: &hooks::Encoder,
+    serializer: S,
+) -> StdResult<StdResult<S::Ok, S::Error>, S> {
+    if let Ok(tagged) = ud.borrow::<Tagged>() {
+        let value = (tagged.tag.as_str(), encoder.serializable(tagged.value.clone()));
+        return Ok(serializer.serialize_newtype_variant("Tagged", 0, TAGGED_VARIANT, &value));
+    }
+    if let Ok(obj) = ud.borrow::<YamlObject>() {
+        let value = SerializeValue {
+            value: obj.current(),
+            tags: true,
+        };
+        return Ok(value.serialize(serializer));
+    }
+    Err(serializer)
+}
+
+/// Converts a serializable Lua value into a YAML value.
+pub(crate) fn to_yaml_value(value: impl Serialize) -> serde_yaml::Result<serde_yaml::Value> {
+    let mut yaml = serde_yaml::to_value(value)?;
+    restore_tags(&mut yaml);
+    Ok(yaml)
+}
+
+fn restore_tags(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Sequence(seq) => seq.iter_mut().for_each(restore_tags),
+        serde_yaml::Value::Mapping(map) => {
+            if map.keys().any(|key| matches!(key, serde_yaml::Value::Tagged(_))) {
+                let entries = mem::take(map).into_iter();
+                *map = entries
+                    .map(|(mut key, mut value)| {
+                        restore_tags(&mut key);
+                        restore_tags(&mut value);
+                        (key, value)
+                    })
+                    .collect();
+            } else {
+                map.values_mut().for_each(restore_tags);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => {
+            if tagged.tag == TAGGED_VARIANT
+                && let serde_yaml::Value::Sequence(pair) = &mut tagged.value
+                && let [serde_yaml::Value::String(tag), inner] = pair.as_mut_slice()
+            {
+                let tag = Tag::new(mem::take(tag));
+                let value = mem::take(inner);
+                **tagged = TaggedValue { tag, value };
+            }
+            restore_tags(&mut tagged.value);
+        }
+        _ => {}
+    }
+}
+
+impl UserData for Tagged {
+    fn register(registry: &mut mlua::UserDataRegistry<Self>) {
+        registry.add_function("new", |lua, (tag, value): (String, Value)| {
+            Tagged::new(&tag, value).into_lua(lua)
+        });
+
+        registry.add_field_method_get("tag", |_, this| Ok(this.tag.clone()));
+        registry.add_field_method_get("value", |_, this| Ok(this.value.clone()));
+
+        registry.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(format!("{} {}", this.tag, this.value.to_string()?))
+        });
+    }
+}
+
+/// Options for converting decoded YAML values into Lua values.
+#[derive(Default)]
+struct DecodeContext {
+    options: SerializeOptions,
+    tags_as_tables: bool,
+    tag_handlers: Option<Table>,
+}
+
+impl DecodeContext {
+    /// Builds the context from the `decode` options table.
+    fn new(opts: Option<&Table>) -> Result<Self> {
+        let mut options = SerializeOptions::new();
+        if let Some(enabled) = opts.and_then(|t| t.raw_get::<bool>("set_array_metatable").ok()) {
+            options = options.set_array_metatable(enabled);
+        }
+        if let Some(enabled) = opts.and_then(|t| t.raw_get::<bool>("null_as_nil").ok()) {
+            options = options
+                .serialize_unit_to_null(!enabled)
+                .serialize_none_to_null(!enabled);
+        }
+        let tags_as_tables = opts.and_then(|t| t.raw_get::<bool>("tags_as_tables").ok()) == Some(true);
+        let tag_handlers: Option<Table> = opt_param!(opts, "tag_handlers")?;
+        Ok(DecodeContext {
+            options,
+            tags_as_tables,
+            tag_handlers,
+        })
+    }
+}
+
+/// Converts a YAML value into a Lua value.
+///
+/// Mapping keys that cannot be used as Lua table keys (`nil` and NaN) are reported as data errors.
+fn yaml_to_lua(
+    lua: &Lua,
+    value: &serde_yaml::Value,
+    ctx: &DecodeContext,
+) -> Result<StdResult<Value, DecodeError>> {
+    let value = match value {
+        serde_yaml::Value::Null if ctx.options.serialize_unit_to_null => Value::NULL,
+        serde_yaml::Value::Null => Value::Nil,
+        serde_yaml::Value::Bool(b) => Value::Boolean(*b),
+        serde_yaml::Value::Number(n) => match n.as_i64() {
+            Some(n) => Value::Integer(n),
+            None => Value::Number(n.as_f64().unwrap_or_default()),
+        },
+        serde_yaml::Value::String(s) => Value::String(lua.create_string(s)?),
+        serde_yaml::Value::Sequence(seq) => {
+            let table = lua.create_table_with_capacity(seq.len(), 0)?;
+            for (i, value) in seq.iter().enumerate() {
+                table.raw_set(i + 1, lua_try!(yaml_to_lua(lua, value, ctx)?, convert::identity))?;
+            }
+            if ctx.options.set_array_metatable {
+                table.set_metatable(Some(lua.array_metatable()))?;
+            }
+            Value::Table(table)
+        }
+        serde_yaml::Value::Mapping(map) => {
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            for (key, value) in map {
+                let key = lua_try!(yaml_to_lua(lua, key, ctx)?, convert::identity);
+                match key {
+                    Value::Nil => return Ok(Err(DecodeError::data("mapping key cannot be nil"))),
+                    Value::Number(n) if n.is_nan() => {
+                        return Ok(Err(DecodeError::data("mapping key cannot be NaN")));
+                    }
+                    _ => {}
+                }
+                table.raw_set(key, lua_try!(yaml_to_lua(lua, value, ctx)?, convert::identity))?;
+            }
+            Value::Table(table)
+        }
+        serde_yaml::Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let value = lua_try!(yaml_to_lua(lua, &tagged.value, ctx)?, convert::identity);
+            if let Some(handler) = (ctx.tag_handlers.as_ref())
+                .map(|handlers| handlers.raw_get::<Option<Function>>(&*tag))
+                .transpose()?
+                .flatten()
+            {
+                return Ok(Ok(handler.call((value, tag))?));
+            }
+            match ctx.tags_as_tables {
+                true => Value::Table(lua.create_table_from([
+                    ("tag", Value::String(lua.create_string(&tag)?)),
+                    ("value", value),
+                ])?),
+                false => Tagged::new(&tag, value).into_lua(lua)?,
+            }
+        }
+    };
+    Ok(Ok(value))
+}
+
+/// Parses all documents of a YAML stream.
+fn parse_all(data: &[u8]) -> StdResult<Vec<serde_yaml::Value>, DecodeError> {
+    let mut docs = Vec::new();
+    for document in serde_yaml::Deserializer::from_slice(data) {
+        let mut yaml =
+            serde_yaml::Value::deserialize(document).map_err(|err| DecodeError::from_yaml(err, data))?;
+        yaml.apply_merge().map_err(DecodeError::data)?;
+        docs.push(yaml);
+    }
+    Ok(docs)
+}
+
+/// Decodes a YAML string or byte array into a Lua value.
+///
+/// The `opts` table can contain the following options:
+/// - `set_array_metatable` (boolean): If true, sets a metatable for arrays. Default is false.
+/// - `null_as_nil` (boolean): If true, `null`s will be represented as Lua `nil`. Default is false.
+///   Mappings with a `null` key cannot be decoded with this option.
+/// - `tags_as_tables` (boolean): If true, tagged values are represented as `{ tag = ..., value = ... }`
+///   tables instead of `Tagged` userdata. Default is false.
+/// - `tag_handlers` (table): A table of `handler(value, tag)` functions indexed by tag (e.g.
+///   `["!include"]`), whose results replace the tagged values.
+/// - `reviver` (function): A `reviver(key, value)` function that is called for every decoded value
+///   (from the innermost to the root) and returns the value to use instead.
+///
+/// On failure, returns `nil` and a [`DecodeError`] with the location of the error.
+pub fn decode(
+    lua: &Lua,
+    (data, opts): (StringOrBytes, Option<Table>),
+) -> Result<StdResult<Value, DecodeError>> {
+    let ctx = DecodeContext::new(opts.as_ref())?;
+    let data = data.as_bytes_deref();
+    let mut yaml: serde_yaml::Value = lua_try!(serde_yaml::from_slice(&data), |err| {
+        DecodeError::from_yaml(err, &data)
+    });
+    lua_try!(yaml.apply_merge(), DecodeError::data);
+    let value = lua_try!(yaml_to_lua(lua, &yaml, &ctx)?, convert::identity);
+    let reviver: Option<Function> = opt_param!(opts, "reviver")?;
+    match reviver {
+        Some(reviver) => Ok(Ok(hooks::revive(lua, value, &reviver)?)),
+        None => Ok(Ok(value)),
+    }
+}
+
+/// Decodes a YAML string or bytes as a native Rust object.
+///
+/// The returned value can be a primitive type or userdata.
+pub fn decode_native(lua: &Lua, data: StringOrBytes) -> Result<StdResult<Value, DecodeError>> {
+    let data = data.as_bytes_deref();
+    let mut yaml: serde_yaml::Value = lua_try!(serde_yaml::from_slice(&data), |err| {
+        DecodeError::from_yaml(err, &data)
+    });
+    lua_try!(yaml.apply_merge(), DecodeError::data);
+    Ok(Ok(lua_try!(
+        YamlObject::from(yaml).into_lua(lua),
+        DecodeError::data
+    )))
+}
+
+/// Encodes a Lua value into a YAML string.
+///
+/// The optional `opts` table can contain:
+/// - `relaxed` (boolean): If true, skip recursive tables and unsupported types. Default is false.
+/// - `replacer` (function): A `replacer(key, value)` function that is called for every value and
+///   returns the value to encode instead (or `nil` to skip it).
+/// - `sort_keys` (boolean): If true, sort mapping keys. Default is false.
+/// - `indent` (number): Number of spaces to indent nested mappings with. Default is 2.
+/// - `indent_sequences` (boolean): If true, indent sequences nested in mappings instead of
+///   aligning them with the parent key. Default is false.
+/// - `flow_level` (number): Nesting level from which collections are written in flow style
+///   (`[1, 2]` and `{a: 1}`), where 0 is the top-level value. Default is block style everywhere.
+/// - `quote_style` (string): Quoting of string values: `auto` (only when needed), `single` or
+///   `double`. Default is `auto`.
+/// - `line_width` (number): Preferred maximum line width, longer strings are folded at spaces.
+///   Default is unlimited.
+/// - `explicit_start` (boolean): If true, start the document with a `---` marker. Default is false.
+///
+/// Tables and userdata with a `__serialize` metamethod are encoded as the value returned by the
+/// metamethod.
+pub fn encode(lua: &Lua, (value, opts): (Value, Option<Table>)) -> Result<StdResult<String, String>> {
+    let replacer: Option<Function> = opt_param!(opts, "replacer")?;
+    let opts = opts.as_ref();
+    let relaxed = opts.and_then(|t| t.raw_get::<bool>("relaxed").ok()) == Some(true);
+    let sort_keys = opts.and_then(|t| t.raw_get::<bool>("sort_keys").ok()) == Some(true);
+    let encoder = hooks::Encoder::new(lua, ENCODE_METAMETHODS, replacer.as_ref())
+        .relaxed(relaxed)
+        .sort_keys(sort_keys)
+        .yaml_tags(true);
+    let value = encoder.wrap(value)?;
+
+    let emitter = Emitter::new(opts)?;
+    let yaml = lua_try!(encoder.finish(to_yaml_value(&value))?);
+    if emitter.is_default() {
+        return Ok(serde_yaml::to_string(&yaml).map_err(|e| e.to_string()));
+    }
+    Ok(Ok(emitter.emit(&yaml)?))
+}
+
+/// Decodes a multi-document YAML stream into a sequence of Lua values.
+///
+/// Accepts the same options as [`decode`]. The `reviver` function is applied to every document.
+pub fn decode_all(
+    lua: &Lua,
+    (data, opts): (StringOrBytes, Option<Table>),
+) -> Result<StdResult<Table, DecodeError>> {
+    let ctx = DecodeContext::new(opts.as_ref())?;
+    let reviver: Option<Function> = opt_param!(opts, "reviver")?;
+    let docs = match parse_all(&data.as_bytes_deref()) {
+        Ok(docs) => docs,
+        Err(err) => return Ok(Err(err)),
+    };
+    let values = lua.create_table_with_capacity(docs.len(), 0)?;
+    for yaml in &docs {
+        let mut value = lua_try!(yaml_to_lua(lua, yaml, &ctx)?, convert::identity);
+        if let Some(reviver) = &reviver {
+            value = hooks::revive(lua, value, reviver)?;
+        }
+        values.raw_push(value)?;
+    }
+    Ok(Ok(values))
+}
+
+/// Decodes a multi-document YAML stream into a sequence of native Rust objects.
+///
+/// All documents share one allocation, so the returned objects are cheap to create.
+pub fn decode_all_native(lua: &Lua, data: StringOrBytes) -> Result<StdResult<Table, DecodeError>> {
+    let docs = match parse_all(&data.as_bytes_deref()) {
+        Ok(docs) => docs,
+        Err(err) => return Ok(Err(err)),
+    };
+    let root = Arc::new(serde_yaml::Value::Sequence(docs));
+    let docs = root.as_sequence().unwrap();
+    let values = lua.create_table_with_capacity(docs.len(), 0)?;
+    for yaml in docs {
+        let value = unsafe { YamlObject::new(&root, yaml) }.into_lua(lua);
+        values.raw_push(lua_try!(value, DecodeError::data))?;
+    }
+    Ok(Ok(values))
+}
+
+/// Encodes a sequence of Lua values into a multi-document YAML stream.
+///
+/// Documents are separated by `---` lines. Accepts the same options as [`encode`].
+pub fn encode_all(lua: &Lua, (seq, opts): (Table, Option<Table>)) -> Result<StdResult<String, String>> {
+    let replacer: Option<Function> = opt_param!(opts, "replacer")?;
+    let relaxed = opts.as_ref().and_then(|t| t.raw_get::<bool>("relaxed").ok()) == Some(true);
+    let sort_keys = opts.as_ref().and_then(|t| t.raw_get::<bool>("sort_keys").ok()) == Some(true);
+    let emitter = Emitter::new(opts.as_ref())?;
+
+    let mut yamls = Vec::new();
+    for (i, value) in seq.sequence_values::<Value>().enumerate() {
+        let encoder = hooks::Encoder::new(lua, ENCODE_METAMETHODS, replacer.as_ref())
+            .relaxed(relaxed)
+            .sort_keys(sort_keys)
+            .yaml_tags(true);
+        let value = encoder.wrap(value?)?;
+        let yaml = encoder.finish(to_yaml_value(&value))?;
+        yamls.push(lua_try!(yaml.map_err(|err| format!("document {}: {err}", i + 1))));
+    }
+
+    let mut output = Vec::new();
+    if emitter.is_default() {
+        let mut serializer = serde_yaml::Serializer::new(&mut output);
+        for yaml in &yamls {
+            lua_try!(yaml.serialize(&mut serializer));
+        }
+    } else {
+        for (i, yaml) in yamls.iter().enumerate() {
+            // Documents after the first one always need a start marker
+            if i > 0 && !emitter.explicit_start {
+                output.extend_from_slice(b"---\n");
+            }
+            output.extend_from_slice(emitter.emit(yaml)?.as_bytes());
+        }
+    }
+    Ok(String::from_utf8(output).map_err(|e| e.to_string()))
+}
+
+/// Parses a YAML string into a document that can be edited without losing comments and formatting.
+///
+/// Values are addressed by JSON Pointer paths, and the text of untouched values is kept as is.
+pub fn document(lua: &Lua, text: LuaString) -> Result<StdResult<AnyUserData, DecodeError>> {
+    match YamlDocument::parse(text.to_str()?.to_owned()) {
+        Ok(doc) => Ok(Ok(lua.create_userdata(doc)?)),
+        Err(err) => Ok(Err(err)),
+    }
+}
+
+/// A loader for the `yaml` module.
+fn loader(lua: &Lua) -> Result<Table> {
+    let t = lua.create_table()?;
+    t.set("decode", lua.create_function(decode)?)?;
+    t.set("decode_native", lua.create_function(decode_native)?)?;
+    t.set("encode", lua.create_function(encode)?)?;
+    t.set("decode_all", lua.create_function(decode_all)?)?;
+    t.set("decode_all_native", lua.create_function(decode_all_native)?)?;
+    t.set("encode_all", lua.create_function(encode_all)?)?;
+    t.set("document", lua.create_function(document)?)?;
+    t.set("Tagged", lua.create_proxy::<Tagged>()?)?;
+    Ok(t)
+}
+
+/// Registers the `yaml` module in the given Lua state.
+pub fn register(lua: &Lua, name: Option<&str>) -> Result<Table> {
+    let name = name.unwrap_or("@yaml");
+    let value = loader(lua)?;
+    lua.register_module(name, &value)?;
+    Ok(value)
+}
+
+mod document;
+mod format;
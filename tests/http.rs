@@ -0,0 +1,77 @@
+#![cfg(feature = "http")]
+
+//! Checks that messages built in Lua convert back to `http` types without losing anything.
+
+use mlua::{AnyUserData, Lua, Result};
+use mlua_stdlib::bytes::BytesBox;
+use mlua_stdlib::http::{Request, Response};
+
+#[test]
+fn request_into_inner() -> Result<()> {
+    let lua = Lua::new();
+    mlua_stdlib::http::register(&lua, None)?;
+
+    let ud = lua
+        .load(
+            r#"
+            local http = require("@http")
+            return http.Request.new({
+                method = "PUT",
+                uri = "/items/1",
+                version = "HTTP/2",
+                headers = { ["X-Tag"] = { "a", "b" }, ["Content-Type"] = "text/plain" },
+                body = "hello\0world",
+            })
+            "#,
+        )
+        .eval::<AnyUserData>()?;
+    let request = ud.take::<Request>()?.into_inner();
+    assert_eq!(request.method(), http::Method::PUT);
+    assert_eq!(request.uri(), "/items/1");
+    assert_eq!(request.version(), http::Version::HTTP_2);
+    let tags = request.headers().get_all("x-tag").iter().collect::<Vec<_>>();
+    assert_eq!(tags, ["a", "b"]);
+    assert_eq!(request.headers()["content-type"], "text/plain");
+    assert_eq!(request.body().as_bytes(), b"hello\0world");
+
+    // And back to Lua
+    let ud = lua
+        .load("return ...")
+        .call::<AnyUserData>(Request::from(request))?;
+    let request = ud.take::<Request>()?.into_inner();
+    assert_eq!(request.method(), http::Method::PUT);
+    assert_eq!(request.version(), http::Version::HTTP_2);
+    assert_eq!(request.body().as_bytes(), b"hello\0world");
+    Ok(())
+}
+
+#[test]
+fn response_into_inner() -> Result<()> {
+    let lua = Lua::new();
+    mlua_stdlib::http::register(&lua, None)?;
+
+    let ud = lua
+        .load(
+            r#"
+            local http = require("@http")
+            return http.Response.new({
+                status = 404,
+                version = "HTTP/1.0",
+                headers = { ["Set-Cookie"] = { "a=1", "b=2" } },
+                body = "not found",
+            })
+            "#,
+        )
+        .eval::<AnyUserData>()?;
+    let response = http::Response::<BytesBox>::from(ud.take::<Response>()?);
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(response.version(), http::Version::HTTP_10);
+    let cookies = response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(cookies, ["a=1", "b=2"]);
+    assert_eq!(response.body().as_bytes(), b"not found");
+    Ok(())
+}
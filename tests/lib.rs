@@ -19,6 +19,8 @@ async fn run_file(modname: &str) -> Result<()> {
     mlua_stdlib::yaml::register(&lua, None)?;
     #[cfg(feature = "regex")]
     mlua_stdlib::regex::register(&lua, None)?;
+    #[cfg(feature = "glob")]
+    mlua_stdlib::glob::register(&lua, None)?;
     #[cfg(feature = "http")]
     mlua_stdlib::http::register(&lua, None)?;
     #[cfg(feature = "task")]
@@ -83,12 +85,17 @@ include_tests! {
     assertions,
     env,
     #[cfg(feature = "json")] json,
+    #[cfg(feature = "json-schema")] json_schema,
     #[cfg(feature = "regex")] regex,
+    #[cfg(feature = "regex-fancy")] regex_fancy,
+    #[cfg(feature = "glob")] glob,
     #[cfg(feature = "yaml")] yaml,
 
     #[cfg(feature = "http")]
     http {
         headers,
+        request,
+        response,
     },
 
     #[cfg(feature = "task")]
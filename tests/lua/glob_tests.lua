@@ -0,0 +1,62 @@
+local glob = require("@glob")
+
+-- Test basic glob matching
+testing:test("glob_basic", function(t)
+    local g = glob.compile("src/**/*.rs")
+    t.assert(g:is_match("src/lib.rs"), "src/lib.rs should match")
+    t.assert(g:is_match("src/regex/mod.rs"), "src/regex/mod.rs should match")
+    t.assert(not g:is_match("tests/lib.rs"), "tests/lib.rs should not match")
+    t.assert(not g:is_match("src/lib.lua"), "src/lib.lua should not match")
+    t.assert_eq(g:pattern(), "src/**/*.rs", "pattern() mismatch")
+    t.assert_eq(tostring(g), "src/**/*.rs", "tostring() mismatch")
+
+    -- Brace expansion and character classes
+    g = glob.compile("*.{lua,luau}")
+    t.assert(g:is_match("init.lua") and g:is_match("init.luau"), "alternates should match")
+    t.assert(not g:is_match("init.rs"), "alternates should not match")
+    t.assert(glob.compile("file[0-9].txt"):is_match("file1.txt"), "character class should match")
+    t.assert(not glob.compile("file[!0-9].txt"):is_match("file1.txt"), "negated class should not match")
+
+    -- Invalid pattern
+    local bad, err = glob.compile("a[")
+    t.assert(bad == nil and err:find("unclosed character class"), "invalid pattern should return error")
+end)
+
+-- Test glob options
+testing:test("glob_options", function(t)
+    t.assert(not glob.compile("*.RS"):is_match("lib.rs"), "glob is case sensitive by default")
+    t.assert(glob.compile("*.RS", { case_insensitive = true }):is_match("lib.rs"), "case_insensitive option")
+
+    t.assert(glob.compile("*.rs"):is_match("src/lib.rs"), "`*` matches `/` by default")
+    local g = glob.compile("*.rs", { literal_separator = true })
+    t.assert(not g:is_match("src/lib.rs"), "literal_separator option")
+    t.assert(g:is_match("lib.rs"), "literal_separator option should match in the same directory")
+end)
+
+-- Test glob sets
+testing:test("glob_set", function(t)
+    local set = glob.set({ "*.rs", "src/**", "*.lua", "Cargo.{toml,lock}" }, { literal_separator = true })
+    t.assert_eq(set:len(), 4, "len should be 4")
+    t.assert(set:is_match("Cargo.toml"), "is_match should have matches")
+    t.assert(not set:is_match("README.md"), "is_match should not have matches")
+    t.assert_eq(table.concat(set:matches("src/lib.rs"), ","), "2", "matches should return 2")
+    t.assert_eq(table.concat(set:matches("build.rs"), ","), "1", "matches should return 1")
+    t.assert_eq(#set:matches("docs/a.md"), 0, "matches should be empty")
+
+    local bad, err = glob.set({ "*.rs", "a[" })
+    t.assert(bad == nil and err:find("unclosed character class"), "invalid pattern should return error")
+end)
+
+-- Test conversion to regex
+testing:test("glob_to_regex", function(t)
+    local re = glob.to_regex("*.{rs,toml}")
+    t.assert(re:is_match("Cargo.toml"), "regex should match Cargo.toml")
+    t.assert(not re:is_match("README.md"), "regex should not match README.md")
+
+    re = glob.to_regex("src/*.rs", { case_insensitive = true, literal_separator = true })
+    t.assert(re:is_match("SRC/LIB.RS"), "regex should keep case_insensitive option")
+    t.assert(not re:is_match("src/a/lib.rs"), "regex should keep literal_separator option")
+
+    local bad, err = glob.to_regex("a[")
+    t.assert(bad == nil and err:find("unclosed character class"), "invalid pattern should return error")
+end)
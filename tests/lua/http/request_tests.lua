@@ -0,0 +1,69 @@
+local http = require("@http")
+
+testing:test("Request basic operations", function(t)
+    local req = http.Request.new({
+        method = "POST",
+        uri = "https://example.com/api/items?limit=10",
+        headers = { ["Content-Type"] = "application/json" },
+        body = '{"name":"item"}',
+    })
+
+    t.assert_eq(req:method(), "POST")
+    t.assert_eq(req:uri(), "https://example.com/api/items?limit=10")
+    t.assert_eq(req:path(), "/api/items")
+    t.assert_eq(req:query(), "limit=10")
+    t.assert_eq(req:version(), "HTTP/1.1")
+    t.assert_eq(req:headers():get("content-type"), "application/json")
+    t.assert_eq(req:body(), '{"name":"item"}')
+    t.assert_eq(tostring(req), "POST https://example.com/api/items?limit=10 HTTP/1.1")
+
+    -- Defaults
+    req = http.Request.new()
+    t.assert_eq(req:method(), "GET")
+    t.assert_eq(req:uri(), "/")
+    t.assert_eq(req:query(), nil)
+    t.assert_eq(req:headers():count(), 0)
+    t.assert_eq(req:body(), "")
+
+    -- Setters
+    req:set_method("PUT")
+    req:set_uri("/items/1")
+    req:set_version("HTTP/2.0")
+    req:set_body("data\0")
+    t.assert_eq(tostring(req), "PUT /items/1 HTTP/2.0")
+    t.assert_eq(req:body(), "data\0")
+
+    -- Headers are copied
+    local headers = req:headers()
+    headers:set("X-Test", "1")
+    t.assert_eq(req:headers():get("X-Test"), nil)
+    req:set_headers(headers)
+    t.assert_eq(req:headers():get("X-Test"), "1")
+    req:set_headers({ ["X-Other"] = { "a", "b" } })
+    t.assert_same(req:headers():get_all("X-Other"), { "a", "b" })
+    t.assert_eq(req:headers():get("X-Test"), nil)
+end)
+
+testing:test("Request errors", function(t)
+    local ok, err = pcall(http.Request.new, { method = "BAD METHOD" })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid `method`")
+
+    ok, err = pcall(http.Request.new, { uri = "http://exa mple.com" })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid `uri`")
+
+    ok, err = pcall(http.Request.new, { version = "HTTP/4" })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid HTTP version 'HTTP/4'")
+
+    ok, err = pcall(http.Request.new, { headers = 123 })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid `headers`")
+
+    local req = http.Request.new()
+    ok, err = pcall(req.set_method, req, "")
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid HTTP method")
+    t.assert_eq(req:method(), "GET")
+end)
@@ -0,0 +1,48 @@
+local http = require("@http")
+
+testing:test("Response basic operations", function(t)
+    local resp = http.Response.new({
+        status = 404,
+        headers = { ["Content-Type"] = "text/plain" },
+        body = "not found",
+    })
+
+    t.assert_eq(resp:status(), 404)
+    t.assert_eq(resp:reason(), "Not Found")
+    t.assert_eq(resp:version(), "HTTP/1.1")
+    t.assert_eq(resp:headers():get("Content-Type"), "text/plain")
+    t.assert_eq(resp:body(), "not found")
+    t.assert_eq(tostring(resp), "HTTP/1.1 404 Not Found")
+
+    -- Defaults
+    resp = http.Response.new()
+    t.assert_eq(resp:status(), 200)
+    t.assert_eq(resp:body(), "")
+    t.assert_eq(tostring(resp), "HTTP/1.1 200 OK")
+
+    -- Setters
+    resp:set_status(599)
+    resp:set_version("HTTP/1.0")
+    resp:set_body("oops")
+    resp:set_headers({ ["Retry-After"] = "120" })
+    t.assert_eq(resp:reason(), nil)
+    t.assert_eq(tostring(resp), "HTTP/1.0 599 <unknown status code>")
+    t.assert_eq(resp:body(), "oops")
+    t.assert_eq(resp:headers():get("retry-after"), "120")
+end)
+
+testing:test("Response errors", function(t)
+    local ok, err = pcall(http.Response.new, { status = 1000 })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid `status`")
+
+    ok, err = pcall(http.Response.new, { version = "HTTP/1.2" })
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid HTTP version 'HTTP/1.2'")
+
+    local resp = http.Response.new()
+    ok, err = pcall(resp.set_status, resp, 99)
+    t.assert_eq(ok, false)
+    t.assert_match(err, "invalid status code")
+    t.assert_eq(resp:status(), 200)
+end)
@@ -0,0 +1,59 @@
+local json = require("@json")
+
+testing:test("schema", function(t)
+    local schema, err = json.schema({
+        type = "object",
+        properties = {
+            name = { type = "string" },
+            version = { type = "integer", minimum = 1 },
+        },
+        required = { "name" },
+    })
+    t.assert_eq(err, nil, err)
+
+    t.assert(schema:is_valid({ name = "plugin", version = 2 }), "value should be valid")
+    t.assert_eq(#schema:validate({ name = "plugin" }), 0)
+
+    local errors = schema:validate({ version = 0 })
+    t.assert_eq(#errors, 2)
+    table.sort(errors, function(a, b)
+        return a.schema_path < b.schema_path
+    end)
+    t.assert_eq(errors[1].instance_path, "/version")
+    t.assert_eq(errors[1].schema_path, "/properties/version/minimum")
+    t.assert_eq(errors[2].instance_path, "")
+    t.assert_eq(errors[2].schema_path, "/required")
+    t.assert(errors[2].message:find("name"), "unexpected error message: " .. errors[2].message)
+
+    -- Native values
+    t.assert(schema:is_valid(json.decode_native('{"name":"x"}')), "native value should be valid")
+    t.assert(not schema:is_valid(json.decode_native('{"name":1}')), "native value should be invalid")
+
+    -- Big numbers are validated as numbers
+    local big = json.decode_native('{"name":"x","version":18446744073709551616}', { big_numbers = true })
+    t.assert(schema:is_valid(big), "big number should be a valid integer")
+    t.assert(not schema:is_valid({ name = "x", version = json.Number.new("0.5") }), "big number should be checked")
+end)
+
+testing:test("schema_string", function(t)
+    local schema, err = json.schema([[
+        {"$schema": "http://json-schema.org/draft-07/schema#", "type": "array", "items": {"type": "number"}}
+    ]])
+    t.assert_eq(err, nil, err)
+    t.assert(schema:is_valid({ 1, 2.5 }), "array of numbers should be valid")
+    local errors = schema:validate({ 1, "2" })
+    t.assert_eq(#errors, 1)
+    t.assert_eq(errors[1].instance_path, "/1")
+
+    -- Explicit draft
+    schema = json.schema('{"type": "string"}', { draft = "7" })
+    t.assert(schema:is_valid("str"), "string should be valid")
+
+    -- Invalid schemas
+    schema, err = json.schema('{"type": 1}')
+    t.assert_eq(schema, nil)
+    t.assert(err ~= nil, "should have an error for invalid schema")
+    schema, err = json.schema("{}", { draft = "1" })
+    t.assert_eq(schema, nil)
+    t.assert_eq(err, "unsupported draft '1'")
+end)
@@ -30,7 +30,7 @@ testing:test("decode", function(t)
     -- Invalid JSON
     value, err = json.decode("{a:1}")
     t.assert_eq(value, nil)
-    t.assert(err:find("key must be a string"), "unexpected error message: " .. err)
+    t.assert(tostring(err):find("key must be a string"), "unexpected error message: " .. tostring(err))
 
     -- No array metatable by default
     value, err = json.decode("[1,2,3]", { set_array_metatable = false })
@@ -110,3 +110,606 @@ testing:test("encode_decode_roundtrip", function(t)
     local alt_str = [[{"array":[1,2,3],"bool":true,"float":1.0,"null":null,"object":{"a":1}}]]
     t.assert_eq(json.encode(value), alt_str, "roundtrip failed")
 end)
+
+testing:test("lines", function(t)
+    local data = '{"a":1}\n\n[1,2]\r\n{bad}\n"str"'
+    local records, errors = {}, {}
+    for n, value, err in json.lines(data) do
+        if err ~= nil then
+            errors[n] = err
+        else
+            records[n] = value
+        end
+    end
+    t.assert_same(records, { [1] = { a = 1 }, [3] = { 1, 2 }, [5] = "str" })
+    t.assert(errors[4]:find("^line 4: key must be a string"), "unexpected error message: " .. tostring(errors[4]))
+
+    -- Native records
+    for _, value in json.lines('{"a":{"b":2}}', { native = true }) do
+        t.assert_eq(type(value), "userdata")
+        t.assert_eq(value.a.b, 2)
+    end
+
+    -- Incremental feed
+    local chunks = { '{"x":', "1}\n", '{"x":2}\n{"x"', ":3}" }
+    local i = 0
+    local xs = {}
+    for _, value in
+        json.lines(function()
+            i = i + 1
+            return chunks[i]
+        end)
+    do
+        table.insert(xs, value.x)
+    end
+    t.assert_eq(table.concat(xs, ","), "1,2,3")
+
+    -- Decode options
+    local values = {}
+    for n, value, err in json.lines('// header\n{"a":1,} // first\n/* skip */\n[2,]', { dialect = "jsonc" }) do
+        t.assert_eq(err, nil, err)
+        values[n] = value
+    end
+    t.assert_same(values, { [2] = { a = 1 }, [4] = { 2 } })
+    for _, value in json.lines('{"n":18446744073709551615}', { big_numbers = true }) do
+        t.assert_eq(tostring(value.n), "18446744073709551615")
+    end
+    for _, value in json.lines('{"n":18446744073709551615}', { big_numbers = true, native = true }) do
+        t.assert_eq(tostring(value.n), "18446744073709551615")
+    end
+    local reviver = function(key, value)
+        return key == "a" and value * 10 or value
+    end
+    for _, value in json.lines('{"a":1}', { reviver = reviver }) do
+        t.assert_eq(value.a, 10)
+    end
+    t.assert(not pcall(json.lines, "{}", { native = true, reviver = reviver }), "reviver with native should fail")
+    t.assert(not pcall(json.lines, "{}", { dialect = "yaml" }), "unknown dialect should fail")
+end)
+
+testing:test("encode_lines", function(t)
+    local data, err = json.encode_lines({ { a = 1 }, { 1, 2 }, "str" })
+    t.assert_eq(err, nil)
+    t.assert_eq(data, '{"a":1}\n[1,2]\n"str"\n')
+
+    data, err = json.encode_lines({ 1, { f = function() end } })
+    t.assert_eq(data, nil)
+    t.assert(err:find("^item 2: cannot serialize <function>"), "unexpected error message: " .. err)
+
+    -- Roundtrip
+    local values = {}
+    for _, value in json.lines(json.encode_lines({ { a = 1 }, { b = 2 } })) do
+        table.insert(values, value)
+    end
+    t.assert_same(values, { { a = 1 }, { b = 2 } })
+end)
+
+testing:test("native_mutation", function(t)
+    local doc = json.decode_native('{"a":1,"items":[1,2,3],"obj":{"x":1}}')
+
+    -- Assign and remove keys
+    doc.a = 2
+    doc.b = { c = true }
+    t.assert_eq(doc.a, 2)
+    t.assert_eq(doc.b.c, true)
+    doc.a = nil
+    t.assert_eq(doc.a, nil)
+
+    -- Pointer-based updates
+    doc:set("/items/0", 10)
+    doc:set("/items/-", 4)
+    doc:set("/obj/y", "z")
+    t.assert_eq(json.encode(doc.items), "[10,2,3,4]")
+    t.assert_eq(doc:pointer("/obj/y"), "z")
+    t.assert(not pcall(doc.set, doc, "/missing/key", 1), "set on missing parent should fail")
+
+    -- Array operations
+    local items = doc.items
+    items:push(5)
+    items:insert(1, 0)
+    t.assert_eq(json.encode(items), "[0,10,2,3,4,5]")
+    t.assert_eq(items:remove(2), 10)
+    items[1] = -1
+    t.assert_eq(json.encode(items), "[-1,2,3,4,5]")
+    t.assert(not pcall(items.insert, items, 10, 1), "insert out of bounds should fail")
+
+    -- Changes made through any handle are visible in the whole document
+    t.assert_eq(json.encode(doc.items), "[-1,2,3,4,5]")
+    local obj = doc.obj
+    doc:set("/obj/x", 2)
+    t.assert_eq(obj.x, 2)
+    doc.obj.x = 3
+    t.assert_eq(obj.x, 3)
+    obj:set("", { x = 4 })
+    t.assert_eq(doc.obj.x, 4)
+    doc.items:push(6)
+    t.assert_eq(#items, 6)
+
+    -- Iteration is not affected by changes made during the loop
+    local keys = {}
+    for key in doc:iter() do
+        doc.extra = true
+        table.insert(keys, key)
+    end
+    table.sort(keys)
+    t.assert_same(keys, { "b", "items", "obj" })
+    doc.extra = nil
+
+    -- Removed values are returned as native objects
+    local removed = doc:remove("obj")
+    t.assert_eq(removed.x, 4)
+    t.assert_eq(doc.obj, nil)
+    t.assert(not pcall(function()
+        return obj.x
+    end), "handles to removed values should fail")
+    t.assert_eq(json.encode(doc), '{"b":{"c":true},"items":[-1,2,3,4,5,6]}')
+end)
+
+testing:test("native_handles", function(t)
+    local doc = json.decode_native('{"items":[{"id":1},{"id":2},{"id":3}],"obj":{"a":{"b":1}}}')
+
+    -- Handles follow their element when the array changes
+    local second = doc.items[2]
+    doc.items:insert(1, { id = 0 })
+    t.assert_eq(second.id, 2)
+    doc.items:remove(1)
+    doc.items:remove(1)
+    t.assert_eq(second.id, 2)
+    t.assert_eq(doc.items[1].id, 2)
+
+    -- Handles to removed or replaced values are invalid
+    local third = doc.items[2]
+    doc.items:remove(1)
+    local ok, err = pcall(function()
+        return second.id
+    end)
+    t.assert(not ok, "handle to a removed element should fail")
+    t.assert(tostring(err):find("value was removed from the document", 1, true), err)
+    t.assert_eq(third.id, 3)
+    local b = doc.obj.a
+    doc.obj = { a = { b = 2 } }
+    t.assert(not pcall(function()
+        return b.b
+    end), "handle to a replaced value should fail")
+    b = doc.obj.a
+    doc:set("/obj/a/b", 3)
+    t.assert_eq(b.b, 3)
+
+    -- `pointer` and `set` are relative to the handle
+    local obj = doc.obj
+    obj:set("/a", { c = 1 })
+    t.assert_eq(obj:pointer("/a/c"), 1)
+    t.assert_eq(doc:pointer("/obj/a/c"), 1)
+    t.assert_eq(obj:pointer("/obj"), nil)
+    t.assert_eq(obj:pointer(""), obj)
+end)
+
+testing:test("patch", function(t)
+    local doc = { a = 1, items = { 1, 2 } }
+    local result, err = json.patch(doc, {
+        { op = "replace", path = "/a", value = 2 },
+        { op = "add", path = "/items/0", value = 0 },
+        { op = "add", path = "/b", value = "new" },
+        { op = "remove", path = "/items/2" },
+    })
+    t.assert_eq(err, nil, err)
+    t.assert_same(result, { a = 2, b = "new", items = { 0, 1 } })
+    t.assert_same(doc, { a = 1, items = { 1, 2 } }, "original document must not be modified")
+
+    -- Native documents
+    local native = json.decode_native('{"a":{"b":1}}')
+    result = json.patch(native, json.decode_native('[{"op":"move","from":"/a/b","path":"/c"}]'))
+    t.assert_eq(type(result), "userdata")
+    t.assert_eq(json.encode(result), '{"a":{},"c":1}')
+    t.assert_eq(native.a.b, 1)
+
+    -- An empty table is an empty list of operations
+    result, err = json.patch({ x = 1 }, {})
+    t.assert_eq(err, nil, err)
+    t.assert_same(result, { x = 1 })
+    result, err = json.patch({ x = 1 }, { { op = "add", path = "/y", value = {} } })
+    t.assert_eq(err, nil, err)
+    t.assert_eq(json.encode(result, { sort_keys = true }), '{"x":1,"y":{}}')
+
+    -- Failed operations
+    result, err = json.patch(doc, { { op = "test", path = "/a", value = 5 } })
+    t.assert_eq(result, nil)
+    t.assert(err:find("value did not match"), "unexpected error message: " .. err)
+    result, err = json.patch(doc, { { op = "remove", path = "/missing" } })
+    t.assert_eq(result, nil)
+    t.assert(err ~= nil, "should have an error for missing path")
+end)
+
+testing:test("merge_patch", function(t)
+    local result, err = json.merge_patch({ a = 1, b = { c = 2, d = 3 } }, { a = json.decode("null"), b = { c = 4 } })
+    t.assert_eq(err, nil, err)
+    t.assert_same(result, { b = { c = 4, d = 3 } })
+
+    local native = json.decode_native('{"a":1,"b":2}')
+    result = json.merge_patch(native, json.decode_native('{"b":null,"c":3}'))
+    t.assert_eq(type(result), "userdata")
+    t.assert_eq(json.encode(result), '{"a":1,"c":3}')
+end)
+
+testing:test("diff", function(t)
+    local a = { a = 1, b = { 1, 2 } }
+    local b = { a = 2, b = { 1, 2, 3 }, c = true }
+    local ops, err = json.diff(a, b)
+    t.assert_eq(err, nil, err)
+    t.assert(#ops > 0, "diff should not be empty")
+    t.assert_same(json.patch(a, ops), b)
+
+    -- Native and identical documents
+    ops = json.diff(json.decode_native('{"a":1}'), { a = 1 })
+    t.assert_eq(#ops, 0)
+end)
+
+testing:test("encode_options", function(t)
+    local value = { b = { 1, 2 }, a = "é/x", c = {} }
+
+    -- Key sorting without pretty printing
+    t.assert_eq(json.encode({ b = 1, a = 2, c = 3 }, { sort_keys = true }), '{"a":2,"b":1,"c":3}')
+
+    -- Custom indentation
+    t.assert_eq(json.encode({ a = { 1 } }, { indent = 4 }), '{\n    "a": [\n        1\n    ]\n}')
+    t.assert_eq(json.encode({ 1, 2 }, { indent = "\t" }), "[\n\t1,\n\t2\n]")
+    t.assert_eq(
+        json.encode(value, { pretty = true, indent = 1 }),
+        '{\n "a": "é/x",\n "b": [\n  1,\n  2\n ],\n "c": {}\n}'
+    )
+    t.assert_eq(json.encode({ 1 }, { pretty = true, sort_keys = false }), "[\n  1\n]")
+
+    -- Escaping
+    t.assert_eq(json.encode("é😀/", { ascii_only = true }), '"\\u00e9\\ud83d\\ude00/"')
+    t.assert_eq(json.encode("a/b\n", { escape_slash = true }), '"a\\/b\\n"')
+    t.assert_eq(json.encode("é/", { ascii_only = true, escape_slash = true }), '"\\u00e9\\/"')
+
+    -- Float formatting
+    t.assert_eq(json.encode({ 3.14159, 1.5, 2 }, { float_precision = 2 }), "[3.14,1.50,2]")
+
+    -- Empty tables
+    t.assert_eq(json.encode({ a = {} }), '{"a":{}}')
+    t.assert_eq(json.encode({ a = {} }, { empty_table_as_array = true }), '{"a":[]}')
+
+    -- Invalid options
+    for _, indent in ipairs({ -1, 2.5, true }) do
+        local ok, err = pcall(json.encode, { 1 }, { indent = indent })
+        t.assert(not ok, "indent " .. tostring(indent) .. " should fail")
+        t.assert(tostring(err):find("`indent` must be a string or a non-negative integer", 1, true), err)
+    end
+    local ok, err = pcall(json.encode, { 1.5 }, { float_precision = "x" })
+    t.assert(not ok, "float_precision of a wrong type should fail")
+    t.assert(tostring(err):find("invalid `float_precision`", 1, true), err)
+end)
+
+testing:test("decode_errors", function(t)
+    local value, err = json.decode('{\n  "a": 1,\n  b: 2\n}')
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "syntax")
+    t.assert_eq(err.line, 3)
+    t.assert_eq(err.column, 3)
+    t.assert_eq(err.offset, 14)
+    t.assert_eq(err.message, "key must be a string at line 3 column 3")
+    t.assert_eq(tostring(err), err.message)
+
+    _, err = json.decode('{"a": [1, 2')
+    t.assert_eq(err.kind, "eof")
+    t.assert_eq(err.line, 1)
+
+    _, err = json.decode_native("[1] 2")
+    t.assert_eq(err.kind, "syntax")
+    t.assert_eq(err.offset, 4)
+end)
+
+testing:test("decode_dialects", function(t)
+    local jsonc = [[
+        // Plugin manifest
+        {
+            "name": "plugin", /* inline */
+            "tags": ["a", "b",],
+            "url": "http://example.com",
+        }
+    ]]
+    local expected = { name = "plugin", tags = { "a", "b" }, url = "http://example.com" }
+
+    local value, err = json.decode(jsonc)
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "syntax")
+
+    value, err = json.decode(jsonc, { dialect = "jsonc" })
+    t.assert_eq(err, nil, err)
+    t.assert_same(value, expected)
+
+    value = json.decode_native(jsonc, { dialect = "jsonc" })
+    t.assert_eq(type(value), "userdata")
+    t.assert_eq(json.encode(value), json.encode(expected, { sort_keys = true }))
+
+    -- Errors keep original locations
+    value, err = json.decode("/* a\nb */ [1,, 2]", { dialect = "jsonc" })
+    t.assert_eq(value, nil)
+    t.assert_eq(err.line, 2)
+    t.assert_eq(err.column, 9)
+
+    -- JSON5
+    local json5 = [[
+        {
+            // Comments and unquoted keys
+            name: 'plugin',
+            tags: ["a", "b",],
+            url: "http://example.com",
+        }
+    ]]
+    value, err = json.decode(json5, { dialect = "json5" })
+    t.assert_eq(err, nil, err)
+    t.assert_same(value, expected)
+    value = json.decode_native("{hex: 0x10, half: .5, plus: +1}", { dialect = "json5" })
+    t.assert_eq(value.hex, 16)
+    t.assert_eq(value.half, 0.5)
+    t.assert_eq(value.plus, 1)
+
+    value, err = json.decode("{a: 1,\n  b: }", { dialect = "json5" })
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "syntax")
+    t.assert_eq(err.line, 2)
+    t.assert_eq(err.column, 6)
+    t.assert_eq(err.offset, 12)
+
+    -- Unknown dialect
+    t.assert(not pcall(json.decode, "{}", { dialect = "xml" }), "unknown dialect should fail")
+end)
+
+testing:test("big_numbers", function(t)
+    local input = '{"id":18446744073709551615,"amount":12345678901234567890.123456789,"small":1,"half":0.5}'
+
+    -- Without the option, precision is lost
+    local value = json.decode(input)
+    t.assert_eq(value.id, 2 ^ 64)
+
+    value = json.decode(input, { big_numbers = true })
+    t.assert_eq(type(value.id), "userdata")
+    t.assert_eq(tostring(value.id), "18446744073709551615")
+    t.assert_eq(tostring(value.amount), "12345678901234567890.123456789")
+    t.assert_eq(value.small, 1)
+    t.assert_eq(value.half, 0.5)
+    t.assert_eq(
+        json.encode(value, { sort_keys = true }),
+        '{"amount":12345678901234567890.123456789,"half":0.5,"id":18446744073709551615,"small":1}'
+    )
+
+    -- Native objects
+    local obj = json.decode_native(input, { big_numbers = true })
+    t.assert_eq(tostring(obj.id), "18446744073709551615")
+    t.assert_eq(tostring(obj:dump().amount), "12345678901234567890.123456789")
+    t.assert_eq(json.encode(obj.id), "18446744073709551615")
+    obj.total = obj.amount + 1
+    t.assert_eq(json.encode(obj.total), "12345678901234567891.123456789")
+    t.assert_eq(json.decode_native(input).id, 2 ^ 64)
+
+    -- Arithmetic and comparison
+    local a = json.Number.new("0.1")
+    local b = json.Number.new("0.2")
+    t.assert_eq(tostring(a + b), "0.3")
+    t.assert_eq(tostring(b - a), "0.1")
+    t.assert_eq(tostring(a * 3), "0.3")
+    t.assert_eq(tostring(1 / json.Number.new("4")), "0.25")
+    t.assert_eq(tostring(-a), "-0.1")
+    t.assert(a + b == json.Number.new("0.30"), "0.1 + 0.2 should equal 0.3")
+    t.assert(a < b and a <= b and not (b < a), "comparison failed")
+    if not _VERSION:find("Luau") then
+        -- Luau does not call comparison metamethods for operands of different types
+        t.assert(value.id > 1, "comparison with Lua number failed")
+    end
+    t.assert_eq(json.Number.new("42"):to_number(), 42)
+    t.assert(not pcall(function()
+        return a / 0
+    end), "division by zero should fail")
+
+    local num, err = json.Number.new("1.")
+    t.assert_eq(num, nil)
+    t.assert_eq(err, "invalid number '1.'")
+
+    -- Numbers can be used in tables and documents
+    t.assert_eq(json.encode({ value.id }), "[18446744073709551615]")
+    obj = json.decode_native("[]")
+    obj:push(value.id)
+    t.assert_eq(json.encode(obj), "[18446744073709551615]")
+
+    -- JSONC keeps big numbers as well, JSON5 does not support them
+    value = json.decode("[1.00000000000000000001, // comment\n 2]", { dialect = "jsonc", big_numbers = true })
+    t.assert_eq(tostring(value[1]), "1.00000000000000000001")
+    value, err = json.decode("[1]", { dialect = "json5", big_numbers = true })
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "data")
+
+    -- Big numbers are plain numbers in patches
+    local ops = json.diff({ x = json.Number.new("1.00000000000000000001") }, { x = json.Number.new("2.5") })
+    t.assert_eq(#ops, 1)
+    t.assert_eq(ops[1].path, "/x")
+    t.assert_eq(ops[1].value, 2.5)
+
+    -- Objects that look like internal representations are kept as is
+    input = '{"user":{"$serde_json::private::RawValue":"{\\"admin\\":true}"},"k":{"$serde_json::private::RawValue":"123"}}'
+    for _, opts in ipairs({ {}, { big_numbers = true } }) do
+        value = json.decode(input, opts)
+        t.assert_eq(value.user.admin, nil)
+        t.assert_eq(value.user["$serde_json::private::RawValue"], '{"admin":true}')
+        t.assert_eq(type(value.k), "table")
+        obj = json.decode_native(input, opts)
+        t.assert_eq(obj:type("k"), "object")
+        t.assert_eq(obj.k["$serde_json::private::RawValue"], "123")
+
+        value = json.decode('{"$serde_json::private::Number":"123"}', opts)
+        t.assert_eq(value["$serde_json::private::Number"], "123")
+        value = json.decode('{"$serde_json::private::Number":"abc"}', opts)
+        t.assert_eq(value["$serde_json::private::Number"], "abc")
+    end
+
+    -- Numbers out of the f64 range are rejected
+    for _, opts in ipairs({ {}, { big_numbers = true } }) do
+        value, err = json.decode("1e400", opts)
+        t.assert_eq(value, nil)
+        t.assert_eq(err.kind, "syntax")
+    end
+
+    -- Other serializers get plain numbers
+    local ok, yaml = pcall(require, "@yaml")
+    if ok then
+        t.assert_eq(yaml.encode({ x = json.Number.new("12345678901234567890.5") }), "x: 1.2345678901234567e19\n")
+        t.assert_eq(yaml.encode({ x = json.Number.new("42") }), "x: 42\n")
+    end
+end)
+
+testing:test("query", function(t)
+    local doc = json.decode_native([[
+        {
+            "store": "shop",
+            "items": [
+                {"name": "pen", "price": 2, "tags": ["office"]},
+                {"name": "book", "price": 12},
+                {"name": "cup", "price": 5}
+            ]
+        }
+    ]])
+
+    local names, err = doc:query("$.items[?(@.price < 10)].name")
+    t.assert_eq(err, nil, err)
+    t.assert_same(names, { "pen", "cup" })
+
+    -- Matches are handles into the same document
+    local items = doc:query("$.items[*]")
+    t.assert_eq(#items, 3)
+    t.assert_eq(type(items[1]), "userdata")
+    t.assert_eq(items[1].tags[1], "office")
+
+    t.assert_same(doc:query("$..price"), { 2, 12, 5 })
+    t.assert_same(doc:query("$.missing"), {})
+
+    -- Queries are relative to the current value
+    t.assert_eq(doc.items:query_one("$[1].name"), "book")
+    t.assert_eq(doc:query_one("$.items[?(@.price > 100)]"), nil)
+    t.assert_eq(doc:query_one("$.items[-1]").name, "cup")
+
+    -- Invalid query
+    local value
+    value, err = doc:query("$.items[")
+    t.assert_eq(value, nil)
+    t.assert(err ~= nil, "invalid query should return an error")
+    value, err = doc:query_one("items")
+    t.assert_eq(value, nil)
+    t.assert(err ~= nil, "invalid query should return an error")
+end)
+
+testing:test("serialization_hooks", function(t)
+    local Point = {}
+    Point.__index = Point
+    Point.__tojson = function(self)
+        return { self.x, self.y }
+    end
+    local function point(x, y)
+        return setmetatable({ x = x, y = y }, Point)
+    end
+
+    -- Metamethods
+    t.assert_eq(json.encode({ a = point(1, 2) }), '{"a":[1,2]}')
+    t.assert_eq(json.encode(point(3, 4)), "[3,4]")
+    local Money = {
+        __serialize = function(self)
+            return string.format("%.2f", self.amount)
+        end,
+    }
+    t.assert_eq(json.encode({ setmetatable({ amount = 1.5 }, Money) }), '["1.50"]')
+    t.assert_eq(json.encode_lines({ point(1, 2), point(3, 4) }), "[1,2]\n[3,4]\n")
+
+    -- Replacer
+    local original = { name = "x", secret = "s", list = { 1, 2, 3 } }
+    local keys = {}
+    local encoded = json.encode(original, {
+        sort_keys = true,
+        replacer = function(key, value)
+            table.insert(keys, key)
+            if key == "secret" or key == 2 then
+                return nil
+            end
+            if type(value) == "number" then
+                return value * 10
+            end
+            return value
+        end,
+    })
+    t.assert_eq(encoded, '{"list":[10,null,30],"name":"x"}')
+    t.assert_eq(keys[1], "")
+    t.assert_eq(original.secret, "s", "original table should not be modified")
+    t.assert_eq(original.list[2], 2, "original table should not be modified")
+
+    -- Reviver
+    local decoded = json.decode('{"p":{"x":1,"y":2},"n":[1,2]}', {
+        reviver = function(key, value)
+            if type(value) == "table" and value.x then
+                return point(value.x, value.y)
+            end
+            if key == 2 then
+                return value + 1
+            end
+            return value
+        end,
+    })
+    t.assert_eq(getmetatable(decoded.p), Point)
+    t.assert_eq(decoded.n[2], 3)
+    t.assert_eq(json.encode(decoded.p), "[1,2]")
+
+    -- Shared tables are encoded every time they appear, recursive tables are rejected
+    local shared = point(5, 6)
+    t.assert_eq(json.encode({ shared, { shared } }), "[[5,6],[[5,6]]]")
+    local recursive = { a = 1 }
+    recursive.self = recursive
+    local data, err = json.encode(recursive)
+    t.assert_eq(data, nil)
+    t.assert(err:find("recursive table"), "unexpected error: " .. tostring(err))
+    t.assert_eq(json.encode(recursive, { relaxed = true }), '{"a":1}')
+
+    -- Errors in hooks are propagated
+    t.assert(not pcall(json.encode, {}, {
+        replacer = function()
+            error("boom")
+        end,
+    }), "replacer errors should be propagated")
+end)
+
+-- Test native object introspection
+testing:test("introspection", function(t)
+    local obj = json.decode_native('{"a": [1, 2, null], "b": {"c": "d"}, "n": null, "big": 18446744073709551616}', {
+        big_numbers = true,
+    })
+
+    -- Length
+    t.assert_eq(#obj.a, 3)
+    t.assert_eq(#obj.b, 1)
+
+    -- Keys
+    t.assert_same(obj.a:keys(), { 1, 2, 3 })
+    t.assert_same(obj.b:keys(), { "c" })
+
+    -- Types
+    t.assert_eq(obj:type(), "object")
+    t.assert_eq(obj:type("a"), "array")
+    t.assert_eq(obj:type("n"), "null")
+    t.assert_eq(obj:type("big"), "number")
+    t.assert_eq(obj:type("missing"), nil)
+    t.assert_eq(obj.b:type("c"), "string")
+    t.assert_eq(obj.a:type(1), "number")
+
+    -- Null checks
+    t.assert_eq(obj:is_null("n"), true)
+    t.assert_eq(obj:is_null("a"), false)
+    t.assert_eq(obj:is_null("missing"), false)
+    t.assert_eq(obj.a:is_null(3), true)
+
+    -- Equality
+    local other = json.decode_native('{"c": "d"}')
+    t.assert(obj.b == other, "objects with the same content should be equal")
+    t.assert(obj.a ~= other, "objects with different content should not be equal")
+
+    -- String conversion
+    t.assert_eq(tostring(obj.b), '{"c":"d"}')
+    t.assert_eq(tostring(obj.a), "[1,2,null]")
+end)
@@ -0,0 +1,107 @@
+local regex = require("@regex")
+
+-- Test lookaround and backreferences
+testing:test("fancy_basic", function(t)
+    local re = regex.new("(\\w+) \\1", { engine = "fancy" })
+    t.assert(re:is_match("hello hello world"), "backreference should match")
+    t.assert(not re:is_match("hello world"), "backreference should not match")
+
+    re = regex.new("\\w+(?=!)", { engine = "fancy" })
+    local caps = re:match("hey, wow!")
+    t.assert_eq(caps[0], "wow", "lookahead match should be 'wow'")
+    t.assert_eq(caps:start(0), 6, "lookahead match should start at 6")
+
+    re = regex.new("(?<=\\$)(?P<amount>\\d+)", { engine = "fancy" })
+    caps = re:match("price: $42")
+    t.assert_eq(caps.amount, "42", "named group after lookbehind")
+    t.assert_eq(#caps, 2, "#caps should be 2")
+    t.assert_same(caps:to_table(), { [0] = "42", "42", amount = "42" }, "to_table() mismatch")
+    t.assert_eq(table.concat(re:capture_names(), ","), "amount", "capture_names() mismatch")
+    t.assert_eq(re:captures_len(), 2, "captures_len() mismatch")
+    t.assert_eq(re:match("price: 42"), nil, "lookbehind should not match")
+
+    -- The default engine rejects lookaround
+    local bad, err = regex.new("\\w+(?=!)")
+    t.assert(bad == nil and err:find("look%-around"), "default engine should reject lookaround")
+
+    bad, err = regex.new("(?<=", { engine = "fancy" })
+    t.assert(bad == nil and err ~= nil, "invalid pattern should return error")
+    bad, err = regex.new("a", { engine = "fancy", crlf = true })
+    t.assert(bad == nil and err:find("crlf"), "crlf option should not be supported")
+    local ok, engine_err = pcall(regex.new, "a", { engine = "pcre" })
+    t.assert(not ok and tostring(engine_err):find("unknown regex engine 'pcre'"), "unknown engine")
+    bad, err = regex.RegexSet.new({ "a" }, { engine = "fancy" })
+    t.assert(bad == nil and err:find("only the default engine"), "regex sets should not support fancy engine")
+end)
+
+-- Test match positions and iterators with the fancy engine
+testing:test("fancy_find_iter", function(t)
+    local re = regex.new("(?<=\\$)\\d+", { engine = "fancy" })
+
+    local i, j = re:find("$1 and $23")
+    t.assert(i == 2 and j == 2, "find() is not 2, 2")
+    i, j = re:find("$1 and $23", 3)
+    t.assert(i == 9 and j == 10, "find() from 3 is not 9, 10")
+    i, j = re:find("$1 and $23", -2)
+    t.assert(i == 9 and j == 10, "lookbehind should see the text before init")
+    t.assert_eq(re:find("1 and 23"), nil, "find() should not have matches")
+    t.assert_eq(re:find("$1", 10), nil, "find() past the end should not have matches")
+
+    local spans = {}
+    for s, e in re:find_iter("$1 22 $333") do
+        table.insert(spans, s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(spans, ","), "2-2,8-10", "find_iter() spans mismatch")
+
+    -- Empty matches right after a match are skipped, and never split a character
+    spans = {}
+    for s, e in regex.new("a*", { engine = "fancy" }):find_iter("baaéb") do
+        table.insert(spans, s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(spans, ","), "1-0,2-3,6-5,7-6", "find_iter() empty matches mismatch")
+
+    local words = {}
+    local re_word = regex.new("(?P<word>\\w+) (?P=word)", { engine = "fancy" })
+    for caps in re_word:captures_iter("go go stop now now") do
+        local s, e = caps:span(0)
+        table.insert(words, caps.word .. "@" .. s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(words, ","), "go@1-5,now@12-18", "captures_iter() mismatch")
+
+    local ok = pcall(re.find_iter, re, "\255")
+    t.assert(not ok, "non UTF-8 text should raise an error")
+end)
+
+-- Test split and replace with the fancy engine
+testing:test("fancy_split_replace", function(t)
+    local re = regex.new(",(?! )", { engine = "fancy" })
+    t.assert_same(re:split("a,b, c,d"), { "a", "b, c", "d" }, "split() mismatch")
+    t.assert_same(re:splitn("a,b,c", 2), { "a", "b,c" }, "splitn() mismatch")
+
+    re = regex.new("(?P<word>\\w+)(?= )", { engine = "fancy" })
+    t.assert_eq(re:replace("foo bar baz", "<$word>"), "<foo> bar baz", "replace() with template")
+    t.assert_eq(re:replace_all("foo bar baz", "<$word>"), "<foo> <bar> baz", "replace_all() with template")
+    t.assert_eq(re:replacen("a b c d", 2, "x"), "x x c d", "replacen() with template")
+    local s, n = re:replacen("a b c d", 0, "x")
+    t.assert(s == "a b c d" and n == 0, "replacen() with zero limit replaces nothing")
+    s, n = re:replace_all("a b c d", "x")
+    t.assert(s == "x x x d" and n == 3, "replace_all() count mismatch")
+
+    s = re:replace_all("foo bar baz", function(caps)
+        return caps.word:upper() .. "@" .. caps:start(0)
+    end)
+    t.assert_eq(s, "FOO@1 BAR@5 baz", "replace_all() with function")
+    t.assert_eq(re:replace_all("foo bar baz", { foo = "1", bar = false }), "1 bar baz", "replace_all() with table")
+
+    -- Options and cache
+    re = regex.new("^(a)\\1$", { engine = "fancy", case_insensitive = true })
+    t.assert(re:is_match("aA"), "case_insensitive option")
+    t.assert(not regex.new("^(a)\\1$", { engine = "fancy" }):is_match("aA"), "cached regex must keep its options")
+    local before = regex.cache_stats()
+    regex.new("^(a)\\1$", { engine = "fancy", case_insensitive = true })
+    t.assert(regex.cache_stats().hits > before.hits, "fancy regexes should be cached")
+
+    -- Only UTF-8 text can be matched
+    local ok = pcall(re.is_match, re, "\255")
+    t.assert(not ok, "non UTF-8 text should raise an error")
+end)
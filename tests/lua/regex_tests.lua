@@ -85,3 +85,238 @@ testing:test("capture_locations", function(t)
     locs = re:captures_read("123")
     t.assert_eq(locs, nil, "locs is not nil")
 end)
+
+-- Test match positions and iterators
+testing:test("find_iter", function(t)
+    local re = regex.new("\\d+")
+
+    local i, j = re:find("ab12cd345")
+    t.assert(i == 3 and j == 4, "find() is not 3, 4")
+    i, j = re:find("ab12cd345", 5)
+    t.assert(i == 7 and j == 9, "find() from 5 is not 7, 9")
+    i, j = re:find("ab12cd345", -2)
+    t.assert(i == 8 and j == 9, "find() from -2 is not 8, 9")
+    t.assert_eq(re:find("abc"), nil, "find() should not have matches")
+    t.assert_eq(re:find("ab12", 10), nil, "find() past the end should not have matches")
+
+    local spans = {}
+    for s, e in re:find_iter("1 22 333") do
+        table.insert(spans, s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(spans, ","), "1-1,3-4,6-8", "find_iter() spans mismatch")
+
+    -- Empty matches right after a match are skipped
+    spans = {}
+    for s, e in regex.new("a*"):find_iter("baaab") do
+        table.insert(spans, s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(spans, ","), "1-0,2-4,6-5", "find_iter() empty matches mismatch")
+
+    local dates = {}
+    local re_date = regex.new("(?P<y>\\d{4})-(?P<m>\\d{2})")
+    for caps in re_date:captures_iter("2014-05, 2015-06") do
+        local s, e = caps:span(0)
+        table.insert(dates, caps.y .. "/" .. caps[2] .. "@" .. s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(dates, ","), "2014/05@1-7,2015/06@10-16", "captures_iter() mismatch")
+
+    local caps = re_date:match("on 2014-05")
+    local s, e = caps:span("m")
+    t.assert(s == 9 and e == 10, "span('m') is not 9, 10")
+    t.assert_eq(caps:span("unknown"), nil, "span of unknown group should be nil")
+end)
+
+-- Test replace, replace_all and replacen
+testing:test("replace", function(t)
+    local re = regex.new("(?P<word>\\w+)")
+
+    t.assert_eq(re:replace_all("foo bar baz", "<$word>"), "<foo> <bar> <baz>", "replace_all() with template")
+    t.assert_eq(re:replacen("foo bar baz", 2, "$1!"), "foo! bar! baz", "replacen() with template")
+    t.assert_eq(re:replacen("foo bar", 0, "x"), "foo bar", "replacen() with zero limit replaces nothing")
+
+    -- replace_all and replacen also return the number of replaced matches, as in string.gsub
+    local s, n = re:replace_all("foo bar baz", "x")
+    t.assert(s == "x x x" and n == 3, "replace_all() count mismatch")
+    s, n = re:replacen("foo bar baz", 2, function(caps) return caps.word ~= "foo" and "x" end)
+    t.assert(s == "foo x baz" and n == 2, "replacen() with function count mismatch")
+    s, n = re:replacen("foo bar", 0, "x")
+    t.assert(s == "foo bar" and n == 0, "replacen() with zero limit count mismatch")
+
+    -- Function replacement receives captures, nil/false keeps the match
+    s = re:replace_all("foo bar baz", function(caps)
+        if caps.word == "bar" then
+            return nil
+        end
+        return caps[0]:upper()
+    end)
+    t.assert_eq(s, "FOO bar BAZ", "replace_all() with function")
+    s = re:replace("foo bar", function(caps)
+        local i, j = caps:span(0)
+        return i .. "-" .. j
+    end)
+    t.assert_eq(s, "1-3 bar", "replace() with function")
+    t.assert_eq(re:replace_all("a b", function() return 1 end), "1 1", "numbers are converted to strings")
+
+    -- Table replacement is looked up by the whole match
+    local vars = { name = "Lua", version = false }
+    t.assert_eq(re:replace_all("name version", vars), "Lua version", "replace_all() with table")
+    t.assert_eq(re:replacen("name name", 1, vars), "Lua name", "replacen() with table")
+
+    -- Empty matches
+    t.assert_eq(regex.new("x*"):replace_all("abc", "-"), "-a-b-c-", "replace_all() with empty matches")
+    t.assert_eq(
+        regex.new("x*"):replace_all("abc", function() return "-" end),
+        "-a-b-c-",
+        "replace_all() with function and empty matches"
+    )
+
+    -- Bytes are preserved
+    t.assert_eq(regex.new("b"):replace_all("a\0b\255", "c"), "a\0c\255", "replace_all() on binary text")
+
+    local ok, err = pcall(re.replace_all, re, "abc", function() return {} end)
+    t.assert(not ok and tostring(err):find("invalid replacement value"), "invalid replacement value")
+    ok = pcall(re.replace_all, re, "abc", true)
+    t.assert(not ok, "invalid replacement type")
+end)
+
+-- Test regex builder options
+testing:test("regex_options", function(t)
+    t.assert(not regex.new("abc"):is_match("ABC"), "default regex is case sensitive")
+    t.assert(regex.new("abc", { case_insensitive = true }):is_match("ABC"), "case_insensitive option")
+    -- The same pattern with different options must not be shared in the cache
+    t.assert(not regex.new("abc"):is_match("ABC"), "cached regex must keep its options")
+
+    local re = regex.new("^\\w+$", { multi_line = true })
+    local lines = {}
+    for s, e in re:find_iter("foo\nbar") do
+        table.insert(lines, s .. "-" .. e)
+    end
+    t.assert_eq(table.concat(lines, ","), "1-3,5-7", "multi_line option")
+
+    t.assert(not regex.new("a.b"):is_match("a\nb"), "dot does not match new line by default")
+    t.assert(regex.new("a.b", { dot_matches_new_line = true }):is_match("a\nb"), "dot_matches_new_line option")
+
+    re = regex.new("\\d+ # digits", { ignore_whitespace = true })
+    t.assert_eq(re:find("ab12"), 3, "ignore_whitespace option")
+
+    t.assert(regex.new("^.$"):is_match("é"), "unicode is enabled by default")
+    t.assert(not regex.new("^.$", { unicode = false }):is_match("é"), "unicode option")
+
+    t.assert(regex.new("^b$", { multi_line = true, crlf = true }):is_match("a\r\nb\r\n"), "crlf option")
+
+    local re_big, err = regex.new("\\w{100}", { size_limit = 1000 })
+    t.assert(re_big == nil and err:find("size limit"), "size_limit option")
+    t.assert(regex.new("\\w{10}", { dfa_size_limit = 1000 }):is_match(string.rep("a", 10)), "dfa_size_limit option")
+
+    local ok, opt_err = pcall(regex.new, "a", { size_limit = "big" })
+    t.assert(not ok and tostring(opt_err):find("invalid `size_limit`"), "invalid option type")
+end)
+
+-- Test regex cache statistics
+testing:test("cache_stats", function(t)
+    local before = regex.cache_stats()
+    t.assert(before.capacity > 0, "cache capacity should be positive")
+
+    -- A pattern that is not used by other tests
+    regex.new("cache_stats_(\\d+)")
+    local after = regex.cache_stats()
+    t.assert(after.misses > before.misses, "compiling a new regex should be a miss")
+    t.assert(after.size >= 1, "cache should contain the compiled regex")
+
+    regex.new("cache_stats_(\\d+)")
+    local cached = regex.cache_stats()
+    t.assert(cached.hits > after.hits, "compiling the same regex again should be a hit")
+    t.assert_eq(type(cached.evictions), "number", "evictions should be a number")
+end)
+
+-- Test captures introspection
+testing:test("captures", function(t)
+    local re = regex.new("(?P<key>\\w+)=(?P<value>\\w+)?(;)?")
+    t.assert_eq(re:captures_len(), 4, "captures_len() should include the whole match")
+    t.assert_eq(table.concat(re:capture_names(), ","), "key,value", "capture_names() mismatch")
+
+    local caps = re:match("x: foo=bar")
+    t.assert_eq(#caps, 4, "#caps should be 4")
+    t.assert_eq(table.concat(caps:names(), ","), "key,value", "names() mismatch")
+    t.assert_eq(caps:start(0), 4, "start(0) should be 4")
+    t.assert_eq(caps:finish(0), 10, "finish(0) should be 10")
+    t.assert_eq(caps:start("value"), 8, "start('value') should be 8")
+    t.assert_eq(caps:finish("key"), 6, "finish('key') should be 6")
+    local i, j = caps:span("key")
+    t.assert(i == 4 and j == 6, "span('key') is not 4, 6")
+    t.assert_eq(caps:start(3), nil, "start() of unmatched group should be nil")
+    t.assert_eq(caps:finish("unknown"), nil, "finish() of unknown group should be nil")
+
+    local tbl = caps:to_table()
+    t.assert_same(tbl, { [0] = "foo=bar", "foo", "bar", key = "foo", value = "bar" }, "to_table() mismatch")
+
+    local entries = {}
+    if _VERSION:find("Luau") then
+        -- Luau uses `__iter` for generalized iteration instead of `__pairs`
+        for k, v in caps do
+            entries[k] = v
+        end
+    else
+        for k, v in pairs(caps) do
+            entries[k] = v
+        end
+    end
+    t.assert_same(entries, tbl, "iteration should cover all entries")
+
+    -- Iteration does not depend on the global `next` function
+    local saved_next = next
+    next = nil
+    local ok, count = pcall(function()
+        local n = 0
+        if _VERSION:find("Luau") then
+            for _ in caps do
+                n = n + 1
+            end
+        else
+            for _ in pairs(caps) do
+                n = n + 1
+            end
+        end
+        return n
+    end)
+    next = saved_next
+    t.assert(ok, count)
+    t.assert_eq(count, 5)
+
+    -- Unmatched optional groups are skipped
+    caps = re:match("foo=")
+    t.assert_same(caps:to_table(), { [0] = "foo=", "foo", key = "foo" }, "to_table() with unmatched groups")
+end)
+
+-- Test RegexSet patterns and captures
+testing:test("regex_set_captures", function(t)
+    local patterns = { "^GET (?P<path>\\S+)", "^(?P<method>POST|PUT) (?P<path>\\S+)", "error" }
+    local set = regex.RegexSet.new(patterns)
+    t.assert_same(set:patterns(), patterns, "patterns() mismatch")
+
+    local re = set:regex(2)
+    t.assert(re:is_match("PUT /a"), "regex(2) should match")
+    t.assert_eq(set:regex(4), nil, "regex(4) should be nil")
+    t.assert_eq(set:regex(0), nil, "regex(0) should be nil")
+
+    local i, caps = set:first_match("POST /api error")
+    t.assert_eq(i, 2, "first_match() index should be 2")
+    t.assert_eq(caps.method, "POST", "first_match() method capture")
+    t.assert_eq(caps.path, "/api", "first_match() path capture")
+    t.assert_eq(caps:start("path"), 6, "first_match() captures should have positions")
+    t.assert_eq(set:first_match("DELETE /"), nil, "first_match() should not have matches")
+
+    -- Builder options
+    set = regex.RegexSet.new({ "^get", "^post" }, { case_insensitive = true })
+    t.assert_eq(table.concat(set:matches("POST /"), ","), "2", "case_insensitive set option")
+    t.assert(set:regex(1):is_match("GET"), "regex(i) should keep the set options")
+    t.assert(not regex.RegexSet.new({ "^get" }):is_match("GET"), "cached sets must keep their options")
+
+    local bad, err = regex.RegexSet.new({ "a", "(" })
+    t.assert(bad == nil and err:find("regex parse error"), "invalid set pattern should return error")
+
+    -- Sets go through the shared cache
+    local before = regex.cache_stats()
+    regex.RegexSet.new({ "^get", "^post" }, { case_insensitive = true })
+    t.assert(regex.cache_stats().hits > before.hits, "compiling the same set again should be a hit")
+end)
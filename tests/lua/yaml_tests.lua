@@ -49,6 +49,21 @@ testing:test("decode", function(t)
     value = yaml.decode("a: ~\nb: 1", { null_as_nil = true })
     t.assert_eq(value.a, nil, "~ should be nil when null_as_nil is true")
     t.assert_eq(value.b, 1)
+
+    -- Keys that cannot be used in Lua tables
+    local err
+    value, err = yaml.decode("~: 1\nb: 2", { null_as_nil = true })
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "data")
+    t.assert(tostring(err):find("nil"), "unexpected error: " .. tostring(err))
+    value, err = yaml.decode(".nan: 1\n")
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "data")
+    t.assert(tostring(err):find("NaN"), "unexpected error: " .. tostring(err))
+    value = yaml.decode("~: 1\nb: 2")
+    for key, v in pairs(value) do
+        t.assert_eq(v, key == "b" and 2 or 1)
+    end
 end)
 
 -- Test decode to native object
@@ -135,3 +150,499 @@ user1:
     t.assert_eq(value.user1.name, "Alice")
     t.assert_eq(value.user1.age, 25)
 end)
+
+testing:test("decode_errors", function(t)
+    local value, err = yaml.decode("a: 1\nb: [1, 2\n")
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "eof")
+    t.assert_eq(err.line, 3)
+    t.assert_eq(err.column, 1)
+    t.assert_eq(err.offset, 14)
+    t.assert(err.message:find("did not find expected"), "unexpected error message: " .. err.message)
+    t.assert_eq(tostring(err), err.message)
+
+    -- Errors before the end of the input are syntax errors
+    value, err = yaml.decode("a: [1, }\nb: 2\n")
+    t.assert_eq(value, nil)
+    t.assert_eq(err.kind, "syntax")
+    t.assert_eq(err.line, 1)
+    _, err = yaml.decode_all("a: 1\n---\nb: 'x\n")
+    t.assert_eq(err.kind, "eof")
+    t.assert_eq(err.line, 4)
+
+    -- Merge errors have no location
+    _, err = yaml.decode_native("a:\n  <<: 1\n")
+    t.assert_eq(err.kind, "data")
+    t.assert_eq(err.line, nil)
+end)
+
+testing:test("query", function(t)
+    local doc = yaml.decode_native([[
+store: shop
+items:
+  - name: pen
+    price: 2
+    tags: [office]
+  - name: book
+    price: 12
+  - name: cup
+    price: 5
+codes:
+  1: one
+  true: yes
+]])
+
+    local names, err = doc:query("$.items[?(@.price < 10)].name")
+    t.assert_eq(err, nil, err)
+    t.assert_same(names, { "pen", "cup" })
+
+    -- Matches are handles into the same document
+    local items = doc:query("$.items[*]")
+    t.assert_eq(#items, 3)
+    t.assert_eq(type(items[1]), "userdata")
+    t.assert_eq(items[1].tags[1], "office")
+
+    t.assert_eq(doc.items:query_one("$[1].name"), "book")
+    t.assert_eq(doc:query_one("$.items[?(@.price > 100)]"), nil)
+
+    -- Non-string keys are matched by their string form
+    t.assert_eq(doc:query_one("$.codes['1']"), "one")
+    t.assert_eq(doc:query_one("$.codes['true']"), "yes")
+
+    -- Keys with the same string form are ambiguous
+    local ambiguous = yaml.decode_native('a:\n  1: int\n  "1": str\nb: [1]\n')
+    local _, query_err = ambiguous:query("$.a['1']")
+    t.assert(query_err:find("ambiguous mapping key '1'", 1, true), query_err)
+    t.assert_same(ambiguous.b:query("$[*]"), { 1 })
+
+    local value
+    value, err = doc:query("$[")
+    t.assert_eq(value, nil)
+    t.assert(err ~= nil, "invalid query should return an error")
+end)
+
+testing:test("serialization_hooks", function(t)
+    local Date = {}
+    Date.__index = Date
+    Date.__serialize = function(self)
+        return string.format("%04d-%02d-%02d", self.year, self.month, self.day)
+    end
+
+    local value = { date = setmetatable({ year = 2024, month = 1, day = 2 }, Date), skip = true }
+    local encoded = yaml.encode(value, {
+        replacer = function(key, value)
+            if key == "skip" then
+                return nil
+            end
+            return value
+        end,
+    })
+    t.assert_eq(encoded, "date: 2024-01-02\n")
+
+    local decoded = yaml.decode(encoded, {
+        reviver = function(key, value)
+            if key == "date" then
+                local y, m, d = value:match("(%d+)-(%d+)-(%d+)")
+                return setmetatable({ year = tonumber(y), month = tonumber(m), day = tonumber(d) }, Date)
+            end
+            return value
+        end,
+    })
+    t.assert_eq(getmetatable(decoded.date), Date)
+    t.assert_eq(decoded.date.month, 1)
+end)
+
+testing:test("multi_document", function(t)
+    local stream = "kind: Service\nname: web\n---\nkind: Deployment\nreplicas: 3\n---\n- a\n- b\n"
+
+    local docs, err = yaml.decode_all(stream)
+    t.assert_eq(err, nil, err)
+    t.assert_eq(#docs, 3)
+    t.assert_same(docs[1], { kind = "Service", name = "web" })
+    t.assert_eq(docs[2].replicas, 3)
+    t.assert_same(docs[3], { "a", "b" })
+
+    -- Native variant
+    docs, err = yaml.decode_all_native(stream)
+    t.assert_eq(err, nil, err)
+    t.assert_eq(#docs, 3)
+    t.assert_eq(type(docs[1]), "userdata")
+    t.assert_eq(docs[2].kind, "Deployment")
+    t.assert_eq(docs[3][2], "b")
+    t.assert_eq(docs[1]:pointer("/kind"), "Service")
+    t.assert_eq(docs[2]:pointer("/replicas"), 3)
+    t.assert_eq(docs[3]:pointer("/0"), "a")
+    t.assert_eq(docs[1]:pointer("/0"), nil)
+
+    -- A single document, an empty stream is a single null document
+    t.assert_same(yaml.decode_all("a: 1"), { { a = 1 } })
+    t.assert_same(yaml.decode_all("", { null_as_nil = true }), {})
+    t.assert_eq(#yaml.decode_all(""), 1)
+
+    -- Encoding
+    local encoded = yaml.encode_all({ { kind = "Service" }, { 1, 2 } })
+    t.assert_eq(encoded, "kind: Service\n---\n- 1\n- 2\n")
+    t.assert_same(yaml.decode_all(encoded), { { kind = "Service" }, { 1, 2 } })
+    t.assert_eq(yaml.encode_all({}), "")
+
+    -- Errors point to the right document
+    docs, err = yaml.decode_all("a: 1\n---\nb: [\n")
+    t.assert_eq(docs, nil)
+    t.assert_eq(err.line, 4)
+    local ok, encode_err = yaml.encode_all({ {}, print })
+    t.assert_eq(ok, nil)
+    t.assert(encode_err:find("document 2"), encode_err)
+end)
+
+testing:test("tags", function(t)
+    local input = "password: !secret db-pass\nconfig: !include base.yaml\nplain: 1\n"
+
+    -- Tagged values are preserved by default
+    local value, err = yaml.decode(input)
+    t.assert_eq(err, nil, err)
+    t.assert_eq(type(value.password), "userdata")
+    t.assert_eq(value.password.tag, "!secret")
+    t.assert_eq(value.password.value, "db-pass")
+    t.assert_eq(tostring(value.password), "!secret db-pass")
+    t.assert_eq(value.plain, 1)
+
+    -- Tables instead of userdata
+    value = yaml.decode(input, { tags_as_tables = true })
+    t.assert_same(value.config, { tag = "!include", value = "base.yaml" })
+
+    -- Tag handlers
+    value = yaml.decode(input, {
+        tag_handlers = {
+            ["!include"] = function(path, tag)
+                return { included = path, tag = tag }
+            end,
+        },
+    })
+    t.assert_same(value.config, { included = "base.yaml", tag = "!include" })
+    t.assert_eq(value.password.tag, "!secret")
+
+    -- Native objects
+    local obj = yaml.decode_native(input)
+    t.assert_eq(obj.password.tag, "!secret")
+    t.assert_eq(obj.password.value, "db-pass")
+    obj = yaml.decode_native("items: !list [1, 2]")
+    t.assert_eq(obj.items.value[2], 2)
+    t.assert_eq(obj:dump().items.tag, "!list")
+
+    -- Encoding
+    t.assert_eq(yaml.encode({ password = yaml.Tagged.new("!secret", "x") }), "password: !secret x\n")
+    t.assert_eq(yaml.encode({ yaml.Tagged.new("point", { 1, 2 }) }), "- !point\n  - 1\n  - 2\n")
+    local nested = yaml.Tagged.new("!outer", { yaml.Tagged.new("!inner", 1) })
+    t.assert_eq(yaml.encode(nested), "!outer\n- !inner 1\n")
+    t.assert_eq(yaml.encode_all({ nested, nested }), "!outer\n- !inner 1\n--- !outer\n- !inner 1\n")
+    local roundtrip = yaml.decode(yaml.encode(yaml.decode(input)))
+    t.assert_eq(roundtrip.password.tag, "!secret")
+    t.assert_eq(roundtrip.config.value, "base.yaml")
+
+    -- Native objects keep their tags
+    t.assert_eq(yaml.encode(yaml.decode_native("a: !secret x")), "a: !secret x\n")
+    t.assert_eq(yaml.encode({ doc = yaml.decode_native("- !n 1") }), "doc:\n- !n 1\n")
+    t.assert_eq(yaml.encode(yaml.decode_native("a: !secret x"), { indent = 4 }), "a: !secret x\n")
+
+    -- Other serializers get the plain value
+    local ok, json = pcall(require, "@json")
+    if ok then
+        t.assert_eq(json.encode(yaml.decode("a: !x 1")), '{"a":1}')
+        t.assert_eq(json.encode(yaml.decode_native("a: !x [1, !y 2]")), '{"a":[1,2]}')
+    end
+end)
+
+-- Test native object introspection
+testing:test("introspection", function(t)
+    local obj = yaml.decode_native([[
+a: [1, 2, ~]
+b:
+  c: d
+  "x/y": 1
+  "t~": 2
+n: ~
+1: one
+secret: !secret [s]
+]])
+
+    -- Pointer
+    t.assert_eq(obj:pointer("/a/1"), 2)
+    t.assert_eq(obj:pointer("/b/c"), "d")
+    t.assert_eq(obj:pointer("/b/x~1y"), 1)
+    t.assert_eq(obj:pointer("/b/t~0"), 2)
+    t.assert_eq(obj:pointer("/1"), "one")
+    t.assert_eq(obj:pointer("/secret/0"), "s")
+    t.assert_eq(obj:pointer("/a/01"), nil)
+    t.assert_eq(obj:pointer("/a/5"), nil)
+    t.assert_eq(obj:pointer("a"), nil)
+    t.assert_eq(obj.b:pointer("/c"), "d", "pointer should resolve from the current value")
+    t.assert_eq(obj.b:pointer("/b/c"), nil)
+    t.assert_eq(obj:pointer(""):type(), "object")
+
+    -- Length
+    t.assert_eq(#obj.a, 3)
+    t.assert_eq(#obj.b, 3)
+
+    -- Keys
+    t.assert_same(obj.a:keys(), { 1, 2, 3 })
+    local keys = obj:keys()
+    t.assert_eq(#keys, 5)
+    t.assert_eq(keys[4], 1)
+
+    -- Types
+    t.assert_eq(obj:type(), "object")
+    t.assert_eq(obj:type("a"), "array")
+    t.assert_eq(obj:type("n"), "null")
+    t.assert_eq(obj:type("secret"), "tagged")
+    t.assert_eq(obj:type("missing"), nil)
+
+    -- Null checks
+    t.assert_eq(obj:is_null("n"), true)
+    t.assert_eq(obj:is_null("a"), false)
+    t.assert_eq(obj.a:is_null(3), true)
+
+    -- Equality
+    local other = yaml.decode_native("c: d\nx/y: 1\nt~: 2")
+    t.assert(obj.b == other, "objects with the same content should be equal")
+    t.assert(obj.a ~= other, "objects with different content should not be equal")
+
+    -- String conversion
+    t.assert_eq(tostring(obj.a), "- 1\n- 2\n- null\n")
+end)
+
+-- Test comment- and format-preserving documents
+testing:test("document", function(t)
+    local input = [[
+# Helm values
+replicaCount: 1 # number of pods
+
+image:
+  repository: "nginx"
+  tag: 'stable'
+  pullPolicy: IfNotPresent
+
+ports: [80, 443]
+
+env:
+  - name: A
+    value: "1"
+  # second variable
+  - name: B
+    value: "2"
+
+notes: |
+  line one
+  line two
+]]
+    local doc = yaml.document(input)
+    t.assert_eq(tostring(doc), input, "untouched documents should be unchanged")
+
+    -- Reading values
+    t.assert_eq(doc:get("/replicaCount"), 1)
+    t.assert_eq(doc:get("/image/tag"), "stable")
+    t.assert_eq(doc:get("/env/1/name"), "B")
+    t.assert_eq(doc:get("/missing"), nil)
+    t.assert_same(doc:get("/ports"), { 80, 443 })
+
+    -- Replacing scalars keeps comments and quoting style
+    doc:set("/replicaCount", 3)
+    doc:set("/image/repository", "my/nginx")
+    doc:set("/image/tag", "it's")
+    doc:set("/ports/1", 8443)
+    doc:set("/notes", "short")
+    local output = tostring(doc)
+    t.assert(output:find("replicaCount: 3 # number of pods", 1, true), output)
+    t.assert(output:find('repository: "my/nginx"', 1, true), output)
+    t.assert(output:find("tag: 'it''s'", 1, true), output)
+    t.assert(output:find("ports: [80, 8443]", 1, true), output)
+    t.assert(output:find("notes: short\n", 1, true), output)
+    t.assert(output:find("# Helm values\n", 1, true), output)
+    t.assert(output:find("  # second variable\n", 1, true), output)
+
+    -- Adding values
+    doc:set("/image/digest", "sha256:abc")
+    doc:set("/env/-", { name = "C", value = "3" })
+    doc:set("/ports/-", 9000)
+    doc:set("/resources", { limits = { cpu = "100m" } })
+    t.assert_eq(doc:get("/image/digest"), "sha256:abc")
+    t.assert_same(doc:get("/env/2"), { name = "C", value = "3" })
+    t.assert_same(doc:get("/ports"), { 80, 8443, 9000 })
+    t.assert_eq(doc:get("/resources/limits/cpu"), "100m")
+    output = tostring(doc)
+    t.assert(output:find("  pullPolicy: IfNotPresent\n  digest: sha256:abc\n", 1, true), output)
+    t.assert(output:find('    value: "2"\n  - ', 1, true), output)
+    t.assert(output:find("resources:\n  limits:\n    cpu: 100m\n$"), output)
+
+    -- Replacing collections
+    doc:set("/image", { repository = "redis" })
+    t.assert_same(doc:get("/image"), { repository = "redis" })
+    t.assert_eq(doc:get("/replicaCount"), 3)
+
+    -- Removing values
+    t.assert_eq(doc:remove("/replicaCount"), 3)
+    t.assert_eq(doc:remove("/replicaCount"), nil)
+    t.assert_same(doc:remove("/env/0"), { name = "A", value = "1" })
+    t.assert_eq(doc:remove("/ports/0"), 80)
+    t.assert_eq(doc:get("/replicaCount"), nil)
+    t.assert_eq(doc:get("/env/0/name"), "B")
+    t.assert_same(doc:get("/ports"), { 8443, 9000 })
+    output = tostring(doc)
+    t.assert(output:find("^# Helm values\n\nimage:"), output)
+    t.assert(output:find("env:\n  # second variable\n  - name: B\n", 1, true), output)
+    doc:remove("/image/repository")
+    t.assert(tostring(doc):find("image: {}\n", 1, true), tostring(doc))
+
+    -- Errors
+    local ok, err = pcall(doc.set, doc, "/missing/key", 1)
+    t.assert(not ok and tostring(err):find("path '/missing' does not exist", 1, true), tostring(err))
+    ok, err = pcall(doc.set, doc, "/ports/5", 1)
+    t.assert(not ok and tostring(err):find("array index '5' is out of bounds", 1, true), tostring(err))
+    t.assert(not pcall(doc.set, doc, "/env/0/name/x", 1), "set on a scalar should fail")
+
+    local doc2
+    doc2, err = yaml.document("a: [1, 2")
+    t.assert_eq(doc2, nil)
+    t.assert_eq(err.kind, "eof")
+    t.assert_eq(err.line, 2)
+
+    -- Empty documents
+    doc = yaml.document("# only a comment\n")
+    doc:set("", { a = 1 })
+    t.assert_eq(tostring(doc), "# only a comment\na: 1\n")
+end)
+
+testing:test("document_properties", function(t)
+    local doc = yaml.document("a: &x 1\nb: *x\nc: !secret foo\nd: &y !t\n  - 1\n  -   &z 2\n  - [1, !q 2]\ne: {k: &w v}\n")
+
+    -- Anchors and tags of replaced values are kept
+    doc:set("/a", 2)
+    t.assert_eq(doc:get("/b"), 2)
+    doc:set("/c", "bar")
+    doc:set("/d/1", 3)
+    doc:set("/d/2/1", 5)
+    doc:set("/e/k", "u")
+    t.assert_eq(
+        tostring(doc),
+        'a: &x 2\nb: *x\nc: !secret bar\nd: &y !t\n  - 1\n  -   &z 3\n  - [1, !q 5]\ne: {k: &w "u"}\n'
+    )
+    doc:set("/d", { 1 })
+    t.assert(tostring(doc):find("d: &y !t\n  - 1\ne:", 1, true), tostring(doc))
+
+    -- Tags of new values replace the old ones, and are kept in flow collections
+    doc:set("/c", yaml.Tagged.new("!plain", "x"))
+    doc:set("/e/n", yaml.Tagged.new("!n", { 1 }))
+    t.assert(tostring(doc):find("c: !plain x\n", 1, true), tostring(doc))
+    t.assert(tostring(doc):find('e: {k: &w "u", n: !n [1]}', 1, true), tostring(doc))
+    t.assert_eq(doc:get("/c").tag, "!plain")
+
+    -- Sequence indicators are found without looking into anchors, tags and comments
+    doc = yaml.document("- &a-b 1\n# - comment\n- !t-x 2\n- 3\n")
+    doc:set("/1", "y")
+    t.assert_eq(tostring(doc), "- &a-b 1\n# - comment\n- !t-x y\n- 3\n")
+    t.assert_eq(doc:remove("/1").value, "y")
+    t.assert_eq(tostring(doc), "- &a-b 1\n# - comment\n- 3\n")
+end)
+
+-- Test non-string and complex mapping keys
+testing:test("complex_keys", function(t)
+    local input = [[
+1: one
+true: yes
+1.5: float
+? [a, b]
+: sequence
+? {x: 1}
+: mapping
+]]
+
+    -- Table decoding
+    local value = yaml.decode(input)
+    t.assert_eq(value[1], "one")
+    t.assert_eq(value[true], "yes")
+    t.assert_eq(value[1.5], "float")
+    local found = {}
+    for k, v in pairs(value) do
+        if type(k) == "table" then
+            found[v] = k
+        end
+    end
+    t.assert_same(found.sequence, { "a", "b" })
+    t.assert_same(found.mapping, { x = 1 })
+
+    -- Native lookups
+    local obj = yaml.decode_native(input)
+    t.assert_eq(obj[1], "one")
+    t.assert_eq(obj[true], "yes")
+    t.assert_eq(obj[1.5], "float")
+    t.assert_eq(obj["1"], nil)
+    t.assert_eq(obj:type(1), "string")
+    t.assert_eq(yaml.decode_native("[a, b]")[2], "b")
+
+    -- Native iteration
+    local count = 0
+    for k, v in obj:iter() do
+        count = count + 1
+        if v == "sequence" then
+            t.assert_eq(k[1], "a")
+            t.assert_same(k:dump(), { "a", "b" })
+            t.assert_eq(obj[k], "sequence")
+        elseif v == "mapping" then
+            t.assert_eq(k.x, 1)
+            t.assert_eq(obj[k], "mapping")
+        end
+    end
+    t.assert_eq(count, 5)
+    t.assert_eq(#obj:keys(), 5)
+end)
+
+-- Test yaml encode formatting options
+testing:test("encode_options", function(t)
+    local value = {
+        name = "app",
+        ports = { 80, 443 },
+        labels = { tier = "web" },
+    }
+    local sorted = { sort_keys = true }
+
+    -- Key sorting
+    t.assert_eq(yaml.encode(value, sorted), "labels:\n  tier: web\nname: app\nports:\n- 80\n- 443\n")
+
+    -- Indentation
+    t.assert_eq(
+        yaml.encode(value, { sort_keys = true, indent = 4, indent_sequences = true }),
+        "labels:\n    tier: web\nname: app\nports:\n    - 80\n    - 443\n"
+    )
+    t.assert(not pcall(yaml.encode, value, { indent = 0 }), "zero indent should fail")
+    t.assert_eq(yaml.encode({ a = { b = "x\ny" } }, { indent = 4 }), "a:\n    b: |-\n        x\n        y\n")
+    t.assert_eq(yaml.decode(yaml.encode({ a = { b = "  x\ny" } }, { indent = 4 })).a.b, "  x\ny")
+
+    -- Flow style
+    t.assert_eq(
+        yaml.encode(value, { sort_keys = true, flow_level = 1 }),
+        "labels: {tier: web}\nname: app\nports: [80, 443]\n"
+    )
+    t.assert_eq(yaml.encode(value, { sort_keys = true, flow_level = 0 }), "{labels: {tier: web}, name: app, ports: [80, 443]}\n")
+
+    -- Quoting
+    t.assert_eq(yaml.encode({ a = "x", b = "it's" }, { sort_keys = true, quote_style = "single" }), "a: 'x'\nb: 'it''s'\n")
+    t.assert_eq(yaml.encode({ a = "x", b = "1\n2" }, { sort_keys = true, quote_style = "double" }), 'a: "x"\nb: "1\\n2"\n')
+    t.assert_eq(yaml.encode({ a = "true" }, { quote_style = "auto" }), "a: 'true'\n")
+    t.assert(not pcall(yaml.encode, value, { quote_style = "backtick" }), "unknown quote style should fail")
+
+    -- Line width
+    local text = "the quick brown fox jumps over the lazy dog"
+    local encoded = yaml.encode({ text = text }, { line_width = 20 })
+    t.assert_eq(encoded, "text: the quick\n  brown fox jumps\n  over the lazy dog\n")
+    t.assert_eq(yaml.decode(encoded).text, text)
+
+    -- Explicit document start
+    t.assert_eq(yaml.encode({ a = 1 }, { explicit_start = true }), "---\na: 1\n")
+    t.assert_eq(
+        yaml.encode_all({ { a = 1 }, { b = 2 } }, { explicit_start = true }),
+        "---\na: 1\n---\nb: 2\n"
+    )
+    t.assert_eq(yaml.encode_all({ { a = "x" }, { b = "y" } }, { quote_style = "double" }), 'a: "x"\n---\nb: "y"\n')
+
+    -- Default output is unchanged
+    t.assert_eq(yaml.encode(value, sorted), yaml.encode(value, { sort_keys = true, indent = 2, quote_style = "auto" }))
+end)
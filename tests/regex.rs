@@ -0,0 +1,38 @@
+#![cfg(feature = "regex")]
+
+use mlua::{Lua, Result};
+use mlua_stdlib::regex::{CacheMode, Config};
+
+/// Compiles the same regex twice and returns the cache hits, misses, evictions, size and capacity.
+fn cache_stats(mode: CacheMode, size: usize) -> Result<(u64, u64, u64, u64, u64)> {
+    let lua = Lua::new();
+    let config = Config {
+        cache_mode: mode,
+        cache_size: size,
+        ..Config::default()
+    };
+    mlua_stdlib::regex::register_with_config(&lua, None, config)?;
+    lua.load(
+        r#"
+        local regex = require("@regex")
+        regex.new("a+")
+        regex.new("a+")
+        local stats = regex.cache_stats()
+        return stats.hits, stats.misses, stats.evictions, stats.size, stats.capacity
+        "#,
+    )
+    .eval()
+}
+
+#[test]
+fn per_state_cache() -> Result<()> {
+    assert_eq!(cache_stats(CacheMode::PerState, 8)?, (1, 1, 0, 1, 8));
+    Ok(())
+}
+
+#[test]
+fn zero_cache_size_disables_cache() -> Result<()> {
+    assert_eq!(cache_stats(CacheMode::PerState, 0)?, (0, 2, 0, 0, 0));
+    assert_eq!(cache_stats(CacheMode::Disabled, 8)?, (0, 2, 0, 0, 0));
+    Ok(())
+}